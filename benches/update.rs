@@ -0,0 +1,96 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use rtdriver::{Float, Simulation, Vector};
+
+/// The 16×16 `f32` lattice driven by `main`, with one particle displaced so
+/// the coupling terms are nonzero. Run with and without `--features simd` to
+/// compare the SIMD kernel against the scalar one.
+fn update(c: &mut Criterion) {
+    let mut sim = Simulation::<f32, 16>::build()
+        .stiffness(0.1)
+        .origin_stiffness(10.)
+        .finish();
+    sim.set_position(Vector([7, 9]), Vector([0.5, -0.25]))
+        .unwrap();
+
+    c.bench_function("update 16x16 f32", |b| b.iter(|| sim.update(1e-4)));
+}
+
+/// A `SIZE`×`SIZE` lattice with every particle displaced, so that no
+/// coupling term vanishes.
+fn lattice<T: Float, const SIZE: usize>() -> Simulation<T, SIZE> {
+    Simulation::build()
+        .stiffness(T::from(0.1).unwrap())
+        .origin_stiffness(T::from(10.).unwrap())
+        .random_init(T::from(0.5).unwrap(), 1)
+        .finish()
+}
+
+/// Benchmarks `$op` on `sim`, a [`lattice`] of side 16, 64 and 256 in `f32`
+/// and `f64`, in `$group`.
+macro_rules! bench_sizes {
+    ($group:expr, |$sim:ident| $op:expr) => {
+        bench_sizes!(@one $group, $sim, $op, f32, 16, 64, 256);
+        bench_sizes!(@one $group, $sim, $op, f64, 16, 64, 256);
+    };
+    (@one $group:expr, $sim:ident, $op:expr, $t:ty, $($size:literal),+) => {
+        $({
+            let mut $sim = lattice::<$t, $size>();
+            let id = format!("{}x{} {}", $size, $size, stringify!($t));
+            $group.bench_function(id, |b| b.iter(|| $op));
+        })+
+    };
+}
+
+/// The force computation alone.
+fn accelerations(c: &mut Criterion) {
+    let mut group = c.benchmark_group("accelerations");
+    bench_sizes!(group, |sim| sim.refresh_acc());
+    group.finish();
+}
+
+/// A full velocity Verlet step, one force computation plus the kicks and
+/// the drift.
+fn update_sizes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update");
+    bench_sizes!(group, |sim| sim.update(1e-4));
+    group.finish();
+}
+
+/// A 256×256 lattice stepped on thread pools of increasing size.
+#[cfg(feature = "rayon")]
+fn parallel_scaling(c: &mut Criterion) {
+    let mut sim = Simulation::<f32, 256>::build()
+        .stiffness(0.1)
+        .origin_stiffness(10.)
+        .finish();
+    sim.set_position(Vector([128, 128]), Vector([0.5, -0.25]))
+        .unwrap();
+
+    let mut group = c.benchmark_group("update 256x256 f32");
+    let max_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    for threads in (0..)
+        .map(|p| 1 << p)
+        .take_while(|&threads| threads <= max_threads)
+    {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        group.bench_function(format!("{threads} threads"), |b| {
+            b.iter(|| pool.install(|| sim.update(1e-4)))
+        });
+    }
+    group.finish();
+}
+
+#[cfg(not(feature = "rayon"))]
+fn parallel_scaling(_: &mut Criterion) {}
+
+criterion_group!(
+    benches,
+    update,
+    accelerations,
+    update_sizes,
+    parallel_scaling
+);
+criterion_main!(benches);
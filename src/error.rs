@@ -0,0 +1,69 @@
+use thiserror::Error;
+
+use crate::Vector;
+
+/// A particle index outside the simulation grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("particle index is out of bounds")]
+pub struct OutOfBounds;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum MassError {
+    #[error(transparent)]
+    OutOfBounds(#[from] OutOfBounds),
+    #[error("mass must be positive")]
+    NotPositive,
+}
+
+/// A builder parameter rejected by [`SimulationBuilder::try_finish`]. The
+/// `&'static str` names the parameter.
+///
+/// [`SimulationBuilder::try_finish`]: crate::simulator::SimulationBuilder::try_finish
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum BuildError {
+    #[error("{0} must not be negative")]
+    Negative(&'static str),
+    #[error("{0} must not be NaN")]
+    NotANumber(&'static str),
+    #[error("mass must be positive")]
+    NonPositiveMass,
+    #[error("gaussian bump width must be positive")]
+    NonPositiveWidth,
+    #[error("grid shape has more particles than fit in a usize")]
+    TooManyParticles,
+    #[error("initial positions: {0}")]
+    LengthMismatch(#[from] LengthMismatch),
+}
+
+/// A particle whose position or velocity stopped being finite, typically
+/// because the time step is too large for the integrator to be stable.
+#[derive(Debug, Clone, Copy, PartialEq, Error)]
+#[error("particle {index:?} has a non-finite position or velocity")]
+pub struct InstabilityError<const DIMS: usize = { crate::DIMS }> {
+    /// Grid index of the first such particle in row-major order.
+    pub index: Vector<usize, DIMS>,
+}
+
+/// A run stopped by [`Simulation::run_guarded`] because the total energy
+/// drifted too far from its value at the start.
+///
+/// [`Simulation::run_guarded`]: crate::Simulation::run_guarded
+#[derive(Debug, Clone, Copy, PartialEq, Error)]
+#[error("total energy drifted too far at step {step} of the run")]
+pub struct EnergyDriftError<T> {
+    /// Index of the offending step within the run, counting from zero.
+    pub step: usize,
+    /// Total energy after that step.
+    pub energy: T,
+}
+
+/// A sequence of the wrong length, such as the components of a [`Vector`]
+/// or a per-particle field.
+///
+/// [`Vector`]: crate::Vector
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("expected {expected} elements, got {actual}")]
+pub struct LengthMismatch {
+    pub expected: usize,
+    pub actual: usize,
+}
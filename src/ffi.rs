@@ -0,0 +1,147 @@
+//! C bindings, enabled by the `ffi` feature. A C program declares
+//!
+//! ```c
+//! typedef struct RtdSim RtdSim;
+//!
+//! RtdSim *rtd_sim_new(float stiffness, float origin_stiffness);
+//! void rtd_sim_update(RtdSim *sim, float dt);
+//! const float *rtd_sim_positions_ptr(const RtdSim *sim);
+//! size_t rtd_sim_positions_len(const RtdSim *sim);
+//! void rtd_sim_free(RtdSim *sim);
+//! ```
+//!
+//! and links against the library built as a `staticlib` or `cdylib`.
+//!
+//! # Ownership
+//!
+//! [`rtd_sim_new`] returns a handle owned by the caller, who must release
+//! it with exactly one call to [`rtd_sim_free`] and use it no further. The
+//! buffer behind [`rtd_sim_positions_ptr`] belongs to the handle; it stays
+//! valid until the next [`rtd_sim_update`] or [`rtd_sim_free`] on that
+//! handle and must not be written to.
+//!
+//! # Threading
+//!
+//! A handle may be moved to and used from any thread, but calls on the
+//! same handle must not overlap. Distinct handles are independent. An
+//! update runs on Rayon's thread pool when the `rayon` feature is enabled.
+
+use crate::Simulation;
+
+/// Side of the lattice behind an [`RtdSim`].
+pub const SIZE: usize = 64;
+
+/// An opaque handle to a two-dimensional `f32` [`Simulation`] of side
+/// [`SIZE`].
+pub struct RtdSim {
+    inner: Simulation<f32, SIZE>,
+}
+
+/// A lattice at rest with the given spring constants and the other
+/// parameters at their defaults, or null if either is negative or NaN.
+#[no_mangle]
+pub extern "C" fn rtd_sim_new(stiffness: f32, origin_stiffness: f32) -> *mut RtdSim {
+    Simulation::build()
+        .stiffness(stiffness)
+        .origin_stiffness(origin_stiffness)
+        .try_finish()
+        .map_or(std::ptr::null_mut(), |inner| {
+            Box::into_raw(Box::new(RtdSim { inner }))
+        })
+}
+
+/// Takes one step of size `dt`.
+///
+/// # Safety
+///
+/// `sim` must be a live handle from [`rtd_sim_new`], not in use by another
+/// call.
+#[no_mangle]
+pub unsafe extern "C" fn rtd_sim_update(sim: *mut RtdSim, dt: f32) {
+    // SAFETY: the caller guarantees a live, unaliased handle.
+    let sim = unsafe { &mut *sim };
+    sim.inner.update(dt);
+}
+
+/// The positions, flattened to `x, y` pairs in row-major particle order.
+/// Holds [`rtd_sim_positions_len`] floats.
+///
+/// # Safety
+///
+/// `sim` must be a live handle from [`rtd_sim_new`], not being updated or
+/// freed by another call.
+#[no_mangle]
+pub unsafe extern "C" fn rtd_sim_positions_ptr(sim: *const RtdSim) -> *const f32 {
+    // SAFETY: the caller guarantees a live handle.
+    let sim = unsafe { &*sim };
+    sim.inner.position_buffer().as_ptr().cast()
+}
+
+/// The number of floats behind [`rtd_sim_positions_ptr`].
+///
+/// # Safety
+///
+/// As for [`rtd_sim_positions_ptr`].
+#[no_mangle]
+pub unsafe extern "C" fn rtd_sim_positions_len(sim: *const RtdSim) -> usize {
+    // SAFETY: the caller guarantees a live handle.
+    let sim = unsafe { &*sim };
+    std::mem::size_of_val(sim.inner.position_buffer()) / std::mem::size_of::<f32>()
+}
+
+/// Releases a handle. Does nothing for null.
+///
+/// # Safety
+///
+/// `sim` must be null or a live handle from [`rtd_sim_new`], which is dead
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn rtd_sim_free(sim: *mut RtdSim) {
+    if !sim.is_null() {
+        // SAFETY: the handle came from `Box::into_raw` and is freed once.
+        drop(unsafe { Box::from_raw(sim) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Simulation, Vector};
+
+    #[test]
+    fn test_ffi_round_trip() {
+        let mut sim = Simulation::<f32, SIZE>::build()
+            .stiffness(2.)
+            .origin_stiffness(0.5)
+            .finish();
+        sim.set_position(Vector([3, 5]), Vector([1., -0.5]))
+            .unwrap();
+        sim.run(10, 1e-2);
+        let expected: Vec<f32> = sim.particles().flat_map(|(_, pos, _)| pos).collect();
+
+        // Many handles at once, each stepped and freed, so a leak or a double
+        // free shows up under Miri or a sanitizer.
+        let handles: Vec<_> = (0..100).map(|_| rtd_sim_new(2., 0.5)).collect();
+        for &handle in &handles {
+            assert!(!handle.is_null());
+            unsafe {
+                (*handle)
+                    .inner
+                    .set_position(Vector([3, 5]), Vector([1., -0.5]))
+                    .unwrap();
+                for _ in 0..10 {
+                    rtd_sim_update(handle, 1e-2);
+                }
+                let len = rtd_sim_positions_len(handle);
+                assert_eq!(len, SIZE * SIZE * 2);
+                let positions = std::slice::from_raw_parts(rtd_sim_positions_ptr(handle), len);
+                assert_eq!(positions, expected);
+                rtd_sim_free(handle);
+            }
+        }
+
+        assert!(rtd_sim_new(-1., 0.).is_null());
+        assert!(rtd_sim_new(1., f32::NAN).is_null());
+        unsafe { rtd_sim_free(std::ptr::null_mut()) };
+    }
+}
@@ -0,0 +1,257 @@
+//! Conversions between grid indices and flat offsets into the particle
+//! buffers.
+//!
+//! A lattice of side `SIZE` in `DIMS` dimensions stores its
+//! `SIZE.pow(DIMS)` particles flat, in row-major order: the last index
+//! varies fastest. These are the same const parameters as on
+//! [`Simulation`], so a buffer returned by e.g. [`Simulation::state`] can be
+//! walked with [`deindex`] and addressed with [`index`].
+//!
+//! [`index_in`] and [`deindex_in`] are the same conversions for a grid with
+//! its own extent along every axis, given as a runtime `shape`: offsets are
+//! then mixed-radix numbers whose digits are the grid indices. With a shape
+//! of `SIZE` along every axis they agree with the square versions, which
+//! keep their own implementation so the strides fold to constants. A
+//! [`Simulation`] given a shape by [`SimulationBuilder::shape`] lays out its
+//! buffers this way, and [`Simulation::shape`] returns the shape to walk
+//! them with.
+//!
+//! [`Simulation`]: crate::Simulation
+//! [`Simulation::state`]: crate::Simulation::state
+//! [`Simulation::shape`]: crate::Simulation::shape
+//! [`SimulationBuilder::shape`]: crate::simulator::SimulationBuilder::shape
+
+use crate::Vector;
+
+/// Number of particles in a grid of side `SIZE` in `DIMS` dimensions.
+pub(crate) const fn len<const SIZE: usize, const DIMS: usize>() -> usize {
+    SIZE.pow(DIMS as u32)
+}
+
+/// Like [`len`], but `None` if the count does not fit in a `usize`, in
+/// which case no such grid can be allocated.
+fn checked_len<const SIZE: usize, const DIMS: usize>() -> Option<usize> {
+    SIZE.checked_pow(DIMS as u32)
+}
+
+/// Number of particles in a grid of the given shape, or `None` if the count
+/// does not fit in a `usize`, in which case no such grid can be allocated.
+pub fn shape_len<const DIMS: usize>(shape: Vector<usize, DIMS>) -> Option<usize> {
+    shape
+        .iter()
+        .try_fold(1usize, |len, extent| len.checked_mul(extent))
+}
+
+/// Flat offset of each axis in the row-major buffer of a grid of the given
+/// shape: one for the last axis, and the product of the extents of all
+/// later axes for the others.
+pub(crate) fn shape_strides<const DIMS: usize>(shape: Vector<usize, DIMS>) -> Vector<usize, DIMS> {
+    let mut strides = Vector::broadcast(1);
+    for axis in (0..DIMS.saturating_sub(1)).rev() {
+        strides[axis] = strides[axis + 1] * shape[axis + 1];
+    }
+    strides
+}
+
+/// The flat offset of the particle at `indices` in a grid of the given
+/// shape, or `None` if `indices` lies outside the grid or the particle count
+/// overflows a `usize`.
+pub fn index_in<const DIMS: usize>(
+    shape: Vector<usize, DIMS>,
+    indices: Vector<isize, DIMS>,
+) -> Option<usize> {
+    shape_len(shape)?;
+    filter_indices_in(shape, indices).map(|indices| offset_in(shape, indices))
+}
+
+/// The grid indices of the particle at flat offset `k` in a grid of the
+/// given shape, or `None` if `k` is not less than the particle count or that
+/// count overflows a `usize`. The inverse of [`index_in`].
+pub fn deindex_in<const DIMS: usize>(
+    shape: Vector<usize, DIMS>,
+    k: usize,
+) -> Option<Vector<usize, DIMS>> {
+    if k < shape_len(shape)? {
+        Some((Vector::broadcast(k) / shape_strides(shape)) % shape)
+    } else {
+        None
+    }
+}
+
+/// Like [`offset`], for a grid of the given shape.
+pub(crate) fn offset_in<const DIMS: usize>(
+    shape: Vector<usize, DIMS>,
+    indices: Vector<usize, DIMS>,
+) -> usize {
+    (indices * shape_strides(shape)).sum()
+}
+
+/// Flat offset of grid indices of a grid of the given shape, or `None` if
+/// they are out of range.
+pub(crate) fn checked_offset_in<const DIMS: usize>(
+    shape: Vector<usize, DIMS>,
+    indices: Vector<usize, DIMS>,
+) -> Option<usize> {
+    (indices.map2(shape, |i, extent| i < extent))
+        .all()
+        .then(|| offset_in(shape, indices))
+}
+
+/// Like [`filter_indices`], for a grid of the given shape.
+pub(crate) fn filter_indices_in<const DIMS: usize>(
+    shape: Vector<usize, DIMS>,
+    indices: Vector<isize, DIMS>,
+) -> Option<Vector<usize, DIMS>> {
+    let inside = indices.map2(shape, |i, extent| 0 <= i && (i as usize) < extent);
+    inside.all().then(|| indices.map(|i| i as usize))
+}
+
+/// Flat offset of each axis in the row-major particle buffers.
+pub(crate) fn strides<const SIZE: usize, const DIMS: usize>() -> Vector<usize, DIMS> {
+    Vector::from_idx(|i| SIZE.pow((DIMS - 1 - i) as u32))
+}
+
+/// Flat offset of grid indices already known to be in range.
+pub(crate) fn offset<const SIZE: usize, const DIMS: usize>(indices: Vector<usize, DIMS>) -> usize {
+    (indices * strides::<SIZE, DIMS>()).sum()
+}
+
+/// `indices` as grid indices of a lattice of side `SIZE`, or `None` if any
+/// of them is negative or not less than `SIZE`.
+pub fn filter_indices<const SIZE: usize, const DIMS: usize>(
+    indices: Vector<isize, DIMS>,
+) -> Option<Vector<usize, DIMS>> {
+    if indices.map(|i| (0..SIZE as isize).contains(&i)).all() {
+        Some(indices.map(|i| i as usize))
+    } else {
+        None
+    }
+}
+
+/// The flat offset of the particle at `indices` in a lattice of side `SIZE`
+/// in `DIMS` dimensions, or `None` if `indices` lies outside the grid or
+/// `SIZE.pow(DIMS)` overflows a `usize`.
+pub fn index<const SIZE: usize, const DIMS: usize>(indices: Vector<isize, DIMS>) -> Option<usize> {
+    checked_len::<SIZE, DIMS>()?;
+    filter_indices::<SIZE, DIMS>(indices).map(offset::<SIZE, DIMS>)
+}
+
+/// The grid indices of the particle at flat offset `k` in a lattice of side
+/// `SIZE` in `DIMS` dimensions, or `None` if `k` is not less than
+/// `SIZE.pow(DIMS)` or that count overflows a `usize`. The inverse of
+/// [`index`].
+pub fn deindex<const SIZE: usize, const DIMS: usize>(k: usize) -> Option<Vector<usize, DIMS>> {
+    if k < checked_len::<SIZE, DIMS>()? {
+        let k = Vector::broadcast(k);
+        Some((k / strides::<SIZE, DIMS>()) % Vector::broadcast(SIZE))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deindex, deindex_in, filter_indices, index, index_in, shape_len};
+    use crate::Vector;
+
+    #[test]
+    fn test_filter_indices() {
+        assert_eq!(filter_indices::<4, 2>(Vector([3, 0])), Some(Vector([3, 0])));
+        assert_eq!(filter_indices::<4, 2>(Vector([4, 0])), None);
+        assert_eq!(filter_indices::<4, 2>(Vector([0, -1])), None);
+    }
+
+    #[test]
+    fn test_index() {
+        assert_eq!(index::<100, 2>(Vector([2, 10])), Some(210));
+        assert_eq!(index::<10, 2>(Vector([2, 10])), None);
+        assert_eq!(index::<10, 2>(Vector([10, 2])), None);
+        assert_eq!(index::<10, 2>(Vector([9, 9])), Some(99));
+        assert_eq!(index::<10, 2>(Vector([0, 0])), Some(0));
+        assert_eq!(index::<10, 2>(Vector([-1, 2])), None);
+        assert_eq!(index::<10, 2>(Vector([2, -1])), None);
+        assert_eq!(index::<10, 2>(Vector([-1, -1])), None);
+
+        assert_eq!(index::<100, 3>(Vector([2, 10, 5])), Some(21_005));
+        assert_eq!(index::<10, 3>(Vector([2, 10, 2])), None);
+        assert_eq!(index::<10, 3>(Vector([10, 2, 2])), None);
+        assert_eq!(index::<10, 3>(Vector([9, 9, 9])), Some(999));
+        assert_eq!(index::<10, 3>(Vector([0, 0, 0])), Some(0));
+        assert_eq!(index::<10, 3>(Vector([-1, 2, 2])), None);
+        assert_eq!(index::<10, 3>(Vector([2, -1, 2])), None);
+        assert_eq!(index::<10, 3>(Vector([-1, 2, -1])), None);
+    }
+
+    #[test]
+    fn test_deindex() {
+        assert_eq!(deindex::<100, 2>(210), Some(Vector([2, 10])));
+        assert_eq!(deindex::<10, 2>(99), Some(Vector([9, 9])));
+        assert_eq!(deindex::<10, 2>(0), Some(Vector([0, 0])));
+
+        assert_eq!(deindex::<5, 3>(99), Some(Vector([3, 4, 4])));
+
+        assert_eq!(deindex::<100, 2>(10000), None);
+        assert_eq!(deindex::<100, 2>(usize::MAX), None);
+
+        assert_eq!(deindex::<100, 3>(1000000), None);
+        assert_eq!(deindex::<100, 3>(usize::MAX), None);
+    }
+
+    #[test]
+    fn test_rectangular_round_trip() {
+        let shape = Vector([4, 3]);
+        assert_eq!(shape_len(shape), Some(12));
+
+        let mut k = 0;
+        for i in 0..4 {
+            for j in 0..3 {
+                assert_eq!(index_in(shape, Vector([i, j])), Some(k));
+                assert_eq!(deindex_in(shape, k), Some(Vector([i as usize, j as usize])));
+                k += 1;
+            }
+        }
+        assert_eq!(deindex_in(shape, 12), None);
+        assert_eq!(index_in(shape, Vector([0, 3])), None);
+        assert_eq!(index_in(shape, Vector([4, 0])), None);
+        assert_eq!(index_in(shape, Vector([-1, 0])), None);
+
+        let shape = Vector([2, 5, 3]);
+        for k in 0..30 {
+            let indices = deindex_in(shape, k).unwrap();
+            assert_eq!(index_in(shape, indices.map(|i| i as isize)), Some(k));
+        }
+        assert_eq!(deindex_in(shape, 30), None);
+        assert_eq!(
+            deindex_in(Vector([256, 64]), 64 * 5 + 7),
+            Some(Vector([5, 7]))
+        );
+
+        for k in 0..125 {
+            assert_eq!(deindex_in(Vector([5; 3]), k), deindex::<5, 3>(k));
+        }
+        assert_eq!(shape_len(Vector([usize::MAX, 2])), None);
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_indexing_near_overflow() {
+        // (2^32 - 1)^2 = 2^64 - 2^33 + 1 particles still fit in a usize.
+        const SIDE: usize = u32::MAX as usize;
+        let last = SIDE * SIDE - 1;
+        assert_eq!(deindex::<SIDE, 2>(last), Some(Vector([SIDE - 1, SIDE - 1])));
+        assert_eq!(deindex::<SIDE, 2>(last + 1), None);
+        let corner = Vector([SIDE as isize - 1, SIDE as isize - 1]);
+        assert_eq!(index::<SIDE, 2>(corner), Some(last));
+
+        // 2^64 particles do not.
+        assert_eq!(deindex::<{ 1 << 32 }, 2>(0), None);
+        assert_eq!(deindex::<{ 1 << 32 }, 2>(usize::MAX), None);
+        assert_eq!(deindex::<{ 1 << 16 }, 4>(12345), None);
+        assert_eq!(index::<{ 1 << 16 }, 4>(Vector([0; 4])), None);
+        assert_eq!(deindex::<2, 64>(1), None);
+        assert_eq!(
+            deindex::<2, 63>(1 << 62),
+            Some(Vector::from_idx(|i| (i == 0) as usize))
+        );
+    }
+}
@@ -1,8 +1,20 @@
+use std::ops::{AddAssign, MulAssign, SubAssign};
+
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod indexing;
+#[cfg(feature = "python")]
+pub mod python;
 pub mod simulator;
 pub mod vector;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-pub use simulator::Simulation;
-pub use vector::Vector;
+pub use simulator::{
+    BoundaryCondition, Connectivity, Edge, Integrator, ProgressSink, Simulation, Snapshot,
+};
+pub use vector::{Vec2, Vec3, Vector};
 
 pub const DIMS: usize = 2;
 
@@ -22,5 +34,63 @@ const fn stencil<const DIMS: usize>() -> [[Vector<isize, DIMS>; 2]; DIMS] {
 
 pub const STENCIL: [[Vector<isize, DIMS>; 2]; DIMS] = stencil();
 
-pub trait Float: num::Float + bytemuck::Pod {}
-impl<T: num::Float + bytemuck::Pod> Float for T {}
+/// The offsets `up` in the box `[-reach, reach]^DIMS` accepted by `keep`,
+/// in `[up, down]` pairs like [`stencil`]: the first nonzero component of
+/// `up` is positive and `down` is `-up`.
+fn stencil_where<const DIMS: usize>(
+    reach: usize,
+    keep: impl Fn(Vector<isize, DIMS>) -> bool,
+) -> Vec<[Vector<isize, DIMS>; 2]> {
+    let side = 2 * reach + 1;
+    let mut stencil = Vec::new();
+    for code in 0..side.pow(DIMS as u32) {
+        let up = Vector::<isize, DIMS>::from_idx(|i| {
+            (code / side.pow((DIMS - 1 - i) as u32) % side) as isize - reach as isize
+        });
+        if up.iter().find(|&i| i != 0).is_some_and(|i| i > 0) && keep(up) {
+            stencil.push([up, -up]);
+        }
+    }
+    stencil
+}
+
+/// Offsets to the diagonal neighbors that differ from the origin by one
+/// along between two and `max_axes` axes.
+fn diagonal_stencil<const DIMS: usize>(max_axes: usize) -> Vec<[Vector<isize, DIMS>; 2]> {
+    stencil_where(1, |up| {
+        (2..=max_axes).contains(&up.iter().filter(|&i| i != 0).count())
+    })
+}
+
+/// Offsets to every particle within Euclidean distance `radius`.
+fn radius_stencil<const DIMS: usize>(radius: usize) -> Vec<[Vector<isize, DIMS>; 2]> {
+    stencil_where(radius, |up| (up * up).sum() <= (radius * radius) as isize)
+}
+
+/// The scalar type of a [`Simulation`]: `f32`, `f64`, or with the `half`
+/// feature `half::f16` and `half::bf16`.
+pub trait Float:
+    num::Float + AddAssign + SubAssign + MulAssign + bytemuck::Pod + Send + Sync
+{
+    /// Identifies the type in checkpoints. Distinct for every implementor,
+    /// including those of the same size.
+    const TAG: u8;
+}
+
+impl Float for f32 {
+    const TAG: u8 = 1;
+}
+
+impl Float for f64 {
+    const TAG: u8 = 2;
+}
+
+#[cfg(feature = "half")]
+impl Float for half::f16 {
+    const TAG: u8 = 3;
+}
+
+#[cfg(feature = "half")]
+impl Float for half::bf16 {
+    const TAG: u8 = 4;
+}
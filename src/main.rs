@@ -1,15 +1,51 @@
+#[cfg(feature = "tqdm")]
+use std::io::IsTerminal;
+
 use rtdriver::Simulation;
 
+#[cfg(not(any(
+    all(
+        feature = "jemalloc",
+        not(feature = "mimalloc"),
+        not(feature = "system")
+    ),
+    all(
+        not(feature = "jemalloc"),
+        feature = "mimalloc",
+        not(feature = "system")
+    ),
+    all(
+        not(feature = "jemalloc"),
+        not(feature = "mimalloc"),
+        feature = "system"
+    ),
+)))]
+compile_error!("enable exactly one of the `jemalloc`, `mimalloc` and `system` features");
+
+#[cfg(all(feature = "jemalloc", not(target_arch = "wasm32")))]
 #[global_allocator]
 static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 
+#[cfg(all(feature = "mimalloc", not(target_arch = "wasm32")))]
+#[global_allocator]
+static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+#[cfg(feature = "system")]
+#[global_allocator]
+static GLOBAL: std::alloc::System = std::alloc::System;
+
 fn main() {
     let mut sim = Simulation::<f32, 16>::build()
         .stiffness(0.1)
         .origin_stiffness(10.)
         .finish();
 
-    for _ in tqdm::tqdm(0..1_000_000) {
-        sim.update(1e-4);
-    }
+    // A progress bar only spams logs that are not a terminal.
+    #[cfg(feature = "tqdm")]
+    let mut progress = std::io::stderr()
+        .is_terminal()
+        .then(rtdriver::simulator::TqdmProgress::default);
+    #[cfg(not(feature = "tqdm"))]
+    let mut progress = ();
+    sim.run_with_progress(1_000_000, 1e-4, &mut progress);
 }
@@ -0,0 +1,315 @@
+//! Python bindings, enabled by the `python` feature. `maturin build` with
+//! the `pyproject.toml` at the root of the repository produces a wheel
+//! whose `rtdriver` module holds the [`PySimulation`] class, exposed to
+//! Python as `Simulation`.
+//!
+//! The side of a [`Simulation`] is a const parameter, so the class picks
+//! one of a fixed set of two-dimensional `f64` lattices at runtime, see
+//! [`SIZES`].
+
+use numpy::{PyArray1, PyArray3, PyArrayMethods};
+use pyo3::{
+    exceptions::{PyIndexError, PyValueError},
+    prelude::*,
+};
+
+use crate::{
+    error::{BuildError, OutOfBounds},
+    simulator::SimulationBuilder,
+    BoundaryCondition, Simulation, Vector,
+};
+
+impl From<OutOfBounds> for PyErr {
+    fn from(error: OutOfBounds) -> Self {
+        PyIndexError::new_err(error.to_string())
+    }
+}
+
+impl From<BuildError> for PyErr {
+    fn from(error: BuildError) -> Self {
+        PyValueError::new_err(error.to_string())
+    }
+}
+
+/// Builder parameters passed from Python; `None` keeps the default.
+struct Parameters {
+    stiffness: Option<f64>,
+    origin_stiffness: Option<f64>,
+    mass: Option<f64>,
+    damping: Option<f64>,
+    boundary: Option<BoundaryCondition>,
+}
+
+impl Parameters {
+    fn apply<const SIZE: usize>(
+        &self,
+        mut builder: SimulationBuilder<f64, SIZE>,
+    ) -> SimulationBuilder<f64, SIZE> {
+        if let Some(stiffness) = self.stiffness {
+            builder = builder.stiffness(stiffness);
+        }
+        if let Some(origin_stiffness) = self.origin_stiffness {
+            builder = builder.origin_stiffness(origin_stiffness);
+        }
+        if let Some(mass) = self.mass {
+            builder = builder.mass(mass);
+        }
+        if let Some(damping) = self.damping {
+            builder = builder.damping(damping);
+        }
+        if let Some(boundary) = self.boundary {
+            builder = builder.boundary(boundary);
+        }
+        builder
+    }
+}
+
+macro_rules! lattices {
+    ($($variant:ident = $size:literal),+$(,)?) => {
+        /// The sides a [`PySimulation`] can be built with.
+        pub const SIZES: &[usize] = &[$($size),+];
+
+        /// A simulation of one of the [`SIZES`].
+        enum Lattice {
+            $($variant(Simulation<f64, $size>)),+
+        }
+
+        impl Lattice {
+            fn build(size: usize, parameters: &Parameters) -> PyResult<Self> {
+                match size {
+                    $($size => Ok(Lattice::$variant(
+                        parameters.apply(Simulation::build()).try_finish()?,
+                    )),)+
+                    _ => Err(PyValueError::new_err(format!(
+                        "size must be one of {SIZES:?}, got {size}"
+                    ))),
+                }
+            }
+        }
+
+        /// Runs `$body` with `$sim` bound to the simulation inside
+        /// `$lattice`, whatever its size.
+        macro_rules! with_lattice {
+            ($lattice:expr, $sim:ident => $body:expr) => {
+                match $lattice {
+                    $(Lattice::$variant($sim) => $body),+
+                }
+            };
+        }
+    };
+}
+
+lattices!(
+    S4 = 4,
+    S8 = 8,
+    S16 = 16,
+    S32 = 32,
+    S64 = 64,
+    S128 = 128,
+    S256 = 256
+);
+
+fn parse_boundary(name: &str) -> PyResult<BoundaryCondition> {
+    match name {
+        "free" => Ok(BoundaryCondition::Free),
+        "periodic" => Ok(BoundaryCondition::Periodic),
+        "fixed" => Ok(BoundaryCondition::Fixed),
+        "reflective" => Ok(BoundaryCondition::Reflective),
+        _ => Err(PyValueError::new_err(format!(
+            "boundary must be \"free\", \"periodic\", \"fixed\" or \"reflective\", got {name:?}"
+        ))),
+    }
+}
+
+/// `buffer`, one vector per particle in row-major order, as a NumPy array
+/// of shape `(size, size, 2)`.
+fn to_numpy<'py>(
+    py: Python<'py>,
+    size: usize,
+    buffer: impl Iterator<Item = Vector<f64>>,
+) -> PyResult<Bound<'py, PyArray3<f64>>> {
+    let flat: Vec<f64> = buffer.flat_map(Vector::iter).collect();
+    PyArray1::from_vec(py, flat).reshape([size, size, 2])
+}
+
+/// A two-dimensional `f64` [`Simulation`] whose side is chosen at runtime
+/// from [`SIZES`].
+#[pyclass(name = "Simulation", module = "rtdriver")]
+pub struct PySimulation {
+    size: usize,
+    lattice: Lattice,
+}
+
+#[pymethods]
+impl PySimulation {
+    /// Builds a lattice of side `size`, one of [`SIZES`]. Unset parameters
+    /// take the defaults of [`SimulationBuilder`]. `boundary` is one of
+    /// `"free"`, `"periodic"`, `"fixed"` and `"reflective"`.
+    #[staticmethod]
+    #[pyo3(signature = (
+        size, *, stiffness=None, origin_stiffness=None, mass=None, damping=None, boundary=None
+    ))]
+    pub fn build(
+        size: usize,
+        stiffness: Option<f64>,
+        origin_stiffness: Option<f64>,
+        mass: Option<f64>,
+        damping: Option<f64>,
+        boundary: Option<&str>,
+    ) -> PyResult<Self> {
+        let parameters = Parameters {
+            stiffness,
+            origin_stiffness,
+            mass,
+            damping,
+            boundary: boundary.map(parse_boundary).transpose()?,
+        };
+        Ok(PySimulation {
+            size,
+            lattice: Lattice::build(size, &parameters)?,
+        })
+    }
+
+    #[getter]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    #[getter]
+    pub fn time(&self) -> f64 {
+        with_lattice!(&self.lattice, sim => sim.time())
+    }
+
+    #[getter]
+    pub fn steps(&self) -> u64 {
+        with_lattice!(&self.lattice, sim => sim.steps())
+    }
+
+    /// See [`Simulation::set_position`]; raises `IndexError` outside the
+    /// grid.
+    pub fn set_position(&mut self, index: (usize, usize), pos: (f64, f64)) -> PyResult<()> {
+        let (index, pos) = (Vector(index.into()), Vector(pos.into()));
+        Ok(with_lattice!(&mut self.lattice, sim => sim.set_position(index, pos))?)
+    }
+
+    /// See [`Simulation::set_velocity`]; raises `IndexError` outside the
+    /// grid.
+    pub fn set_velocity(&mut self, index: (usize, usize), vel: (f64, f64)) -> PyResult<()> {
+        let (index, vel) = (Vector(index.into()), Vector(vel.into()));
+        Ok(with_lattice!(&mut self.lattice, sim => sim.set_velocity(index, vel))?)
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        with_lattice!(&mut self.lattice, sim => sim.update(dt))
+    }
+
+    pub fn run(&mut self, steps: usize, dt: f64) {
+        with_lattice!(&mut self.lattice, sim => sim.run(steps, dt))
+    }
+
+    /// The positions as a new array of shape `(size, size, 2)`.
+    pub fn positions<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray3<f64>>> {
+        with_lattice!(&self.lattice, sim => {
+            to_numpy(py, self.size, sim.particles().map(|(_, pos, _)| pos))
+        })
+    }
+
+    /// The velocities, laid out like [`PySimulation::positions`].
+    pub fn velocities<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray3<f64>>> {
+        with_lattice!(&self.lattice, sim => {
+            to_numpy(py, self.size, sim.particles().map(|(_, _, vel)| vel))
+        })
+    }
+
+    pub fn kinetic_energy(&self) -> f64 {
+        with_lattice!(&self.lattice, sim => sim.kinetic_energy())
+    }
+
+    pub fn potential_energy(&self) -> f64 {
+        with_lattice!(&self.lattice, sim => sim.potential_energy())
+    }
+
+    pub fn total_energy(&self) -> f64 {
+        with_lattice!(&self.lattice, sim => sim.total_energy())
+    }
+}
+
+#[pymodule]
+fn rtdriver(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PySimulation>()?;
+    module.add("SIZES", SIZES)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pyo3::{
+        exceptions::{PyIndexError, PyValueError},
+        prelude::*,
+    };
+
+    use super::{Lattice, PySimulation, SIZES};
+    use crate::{BoundaryCondition, Simulation, Vector};
+
+    #[test]
+    fn test_binding_matches_simulation() {
+        let mut py_sim =
+            PySimulation::build(8, Some(2.), None, None, Some(0.1), Some("periodic")).unwrap();
+        let mut sim = Simulation::<f64, 8>::build()
+            .stiffness(2.)
+            .damping(0.1)
+            .boundary(BoundaryCondition::Periodic)
+            .finish();
+
+        py_sim.set_position((1, 2), (0.5, -0.25)).unwrap();
+        py_sim.set_velocity((3, 3), (0., 1.)).unwrap();
+        sim.set_position(Vector([1, 2]), Vector([0.5, -0.25]))
+            .unwrap();
+        sim.set_velocity(Vector([3, 3]), Vector([0., 1.])).unwrap();
+        py_sim.update(1e-2);
+        py_sim.run(9, 1e-2);
+        sim.run(10, 1e-2);
+
+        assert_eq!(py_sim.size(), 8);
+        assert_eq!(py_sim.steps(), 10);
+        assert_eq!(py_sim.time(), sim.time());
+        assert_eq!(py_sim.kinetic_energy(), sim.kinetic_energy());
+        assert_eq!(py_sim.potential_energy(), sim.potential_energy());
+        assert_eq!(py_sim.total_energy(), sim.total_energy());
+        let Lattice::S8(inner) = &py_sim.lattice else {
+            panic!("built the wrong size");
+        };
+        assert_eq!(inner, &sim);
+    }
+
+    #[test]
+    fn test_binding_errors() {
+        Python::initialize();
+        Python::attach(|py| {
+            let raises = |error: Option<PyErr>, kind: &Bound<'_, _>| {
+                error.is_some_and(|error| error.get_type(py).is(kind))
+            };
+            let value_error = py.get_type::<PyValueError>();
+            let index_error = py.get_type::<PyIndexError>();
+            let build =
+                |size, mass, boundary| PySimulation::build(size, None, None, mass, None, boundary);
+
+            assert!(raises(build(5, None, None).err(), &value_error));
+            assert!(raises(build(8, Some(0.), None).err(), &value_error));
+            assert!(raises(build(8, None, Some("open")).err(), &value_error));
+
+            let mut sim = build(4, None, None).unwrap();
+            assert!(raises(
+                sim.set_position((4, 0), (1., 1.)).err(),
+                &index_error
+            ));
+            assert!(raises(
+                sim.set_velocity((0, 7), (1., 1.)).err(),
+                &index_error
+            ));
+            assert!(SIZES
+                .iter()
+                .all(|&size| build(size, None, Some("reflective")).is_ok()));
+        });
+    }
+}
@@ -1,187 +1,2518 @@
-use crate::{vector::Vector, Float, STENCIL};
+#[cfg(feature = "ndarray")]
+mod arrays;
+mod boundary;
+mod checkpoint;
+mod diagnostics;
+mod export;
+mod forcing;
+#[cfg(feature = "serde")]
+mod grid_serde;
+mod history;
+mod integrator;
+mod neighbors;
+mod progress;
+#[cfg(feature = "simd")]
+mod simd;
+#[cfg(feature = "rustfft")]
+mod spectrum;
+mod subdomain;
+mod thermostat;
 
+pub use boundary::BoundaryCondition;
+pub use forcing::Edge;
+pub use history::Snapshot;
+pub use integrator::Integrator;
+pub use neighbors::Connectivity;
+pub use progress::ProgressSink;
+#[cfg(feature = "tqdm")]
+pub use progress::TqdmProgress;
+
+use forcing::{DrivenEdge, ForceField};
+use history::History;
+use neighbors::Neighbors;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use thermostat::{gaussian, Langevin};
+
+use crate::{
+    error::{
+        BuildError, EnergyDriftError, InstabilityError, LengthMismatch, MassError, OutOfBounds,
+    },
+    indexing::{checked_offset_in, deindex_in, shape_len},
+    vector::Vector,
+    Float,
+};
+
+/// A lattice of particles on a `DIMS`-dimensional grid of side `SIZE`, or
+/// of the extents given to [`SimulationBuilder::shape`]. Particle buffers
+/// are stored flat, in row-major order, see [`crate::indexing`].
 #[derive(Debug, Clone, PartialEq)]
-pub struct Simulation<T: Float, const SIZE: usize> {
-    state: SimulationState<T, SIZE>,
-    tmp_acc: Box<[[Vector<T>; SIZE]; SIZE]>,
+pub struct Simulation<T: Float, const SIZE: usize, const DIMS: usize = { crate::DIMS }> {
+    state: SimulationState<T, SIZE, DIMS>,
+    force_field: ForceField<T, DIMS>,
+    /// Coupling connectivity for `state.boundary`.
+    neighbors: Neighbors,
+    /// Worker threads for the force computation, see
+    /// [`SimulationBuilder::threads`].
+    threads: usize,
+    /// Simulated time, advanced by every step.
+    time: T,
+    /// Steps taken since the simulation was built.
+    steps: u64,
+    integrator: Integrator,
+    min_dt: T,
+    /// The last step accepted by [`Simulation::update_adaptive`].
+    last_adaptive_dt: Option<T>,
+    /// Set when the inputs to `acc` were changed outside of stepping, so
+    /// that the next `update` recomputes it first.
+    acc_stale: bool,
+    tmp_acc: Box<[Vector<T, DIMS>]>,
+    /// Back buffers for `state.pos` and `state.vel`. A step writes the new
+    /// values here while reading the old ones from `state`, then swaps the
+    /// two, so what it reads never changes underneath it.
+    tmp_pos: Box<[Vector<T, DIMS>]>,
+    tmp_vel: Box<[Vector<T, DIMS>]>,
+    /// Running sums of the stage derivatives of `pos` and `vel` for
+    /// [`Integrator::RungeKutta4`], empty until it first steps.
+    rk4_sums: Vec<[Vector<T, DIMS>; 2]>,
+    /// Positions the simulation was built with, restored by
+    /// [`Simulation::reset`].
+    initial_pos: Box<[Vector<T, DIMS>]>,
+    /// Heat bath set by [`Simulation::enable_langevin`].
+    langevin: Option<Langevin<T>>,
+    /// Probes set by [`Simulation::record`].
+    history: Option<History<T, DIMS>>,
+    /// Edge set by [`Simulation::set_driven_edge`].
+    driven: Option<DrivenEdge<T, DIMS>>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct SimulationState<T: Float, const SIZE: usize> {
-    stiffness: T,
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        remote = "Self",
+        bound(
+            serialize = "T: serde::Serialize",
+            deserialize = "T: serde::Deserialize<'de>"
+        )
+    )
+)]
+pub struct SimulationState<T: Float, const SIZE: usize, const DIMS: usize = { crate::DIMS }> {
+    /// Extent of the grid along each axis, `SIZE` along every one unless
+    /// the builder was given a shape.
+    #[cfg_attr(feature = "serde", serde(default = "grid_serde::square::<SIZE, DIMS>"))]
+    shape: Vector<usize, DIMS>,
+    /// Coupling spring constant of the bonds along each axis.
+    stiffness: Vector<T, DIMS>,
+    /// Separation at which a coupling bond exerts no force.
+    rest_length: T,
+    /// Coefficient of the cubic coupling term.
+    cubic_coupling: T,
+    /// Which particles are bonded.
+    connectivity: Connectivity,
+    /// Spring constant of the diagonal bonds added by `connectivity`.
+    diagonal_stiffness: T,
+    /// Bonds reach every particle within this Euclidean distance.
+    coupling_radius: usize,
+    /// Spring constant of each bond beyond the nearest neighbors, in the
+    /// order of [`Connectivity::long_range_stencil`].
+    long_range_stiffness: Box<[T]>,
+    /// Per-particle factor on the coupling; a bond is scaled by the mean of
+    /// its two ends. `None` means uniform.
+    #[cfg_attr(feature = "serde", serde(default))]
+    bond_stiffness: Option<Box<[T]>>,
     origin_stiffness: T,
-    pos: Box<[[Vector<T>; SIZE]; SIZE]>,
-    vel: Box<[[Vector<T>; SIZE]; SIZE]>,
-    acc: Box<[[Vector<T>; SIZE]; SIZE]>,
+    mass: Box<[T]>,
+    damping: T,
+    gravity: Vector<T, DIMS>,
+    boundary: BoundaryCondition,
+    pos: Box<[Vector<T, DIMS>]>,
+    vel: Box<[Vector<T, DIMS>]>,
+    acc: Box<[Vector<T, DIMS>]>,
+    /// Particles held in place by [`Simulation::pin`].
+    pinned: Box<[bool]>,
 }
 
-pub struct SimulationBuilder<T, const SIZE: usize> {
-    stiffness: Option<T>,
+pub struct SimulationBuilder<T, const SIZE: usize, const DIMS: usize = { crate::DIMS }> {
+    shape: Option<Vector<usize, DIMS>>,
+    stiffness: Option<Vector<T, DIMS>>,
+    rest_length: Option<T>,
+    cubic_coupling: Option<T>,
+    connectivity: Option<Connectivity>,
+    diagonal_stiffness: Option<T>,
+    #[allow(clippy::type_complexity)]
+    coupling_radius: Option<(usize, Box<dyn Fn(T) -> T>)>,
     origin_stiffness: Option<T>,
+    mass: Option<T>,
+    damping: Option<T>,
+    gravity: Option<Vector<T, DIMS>>,
+    boundary: Option<BoundaryCondition>,
+    integrator: Option<Integrator>,
+    min_dt: Option<T>,
+    threads: Option<usize>,
+    positions: Option<Box<[Vector<T, DIMS>]>>,
+    random_init: Option<(T, u64)>,
+    gaussian_bump: Option<(Vector<T, DIMS>, T, T)>,
+    thermal_init: Option<(T, u64)>,
 }
 
-impl<T: Float, const SIZE: usize> SimulationBuilder<T, SIZE> {
+impl<T: Float, const SIZE: usize, const DIMS: usize> SimulationBuilder<T, SIZE, DIMS> {
+    /// Extent of the grid along each axis, in place of `SIZE` along every
+    /// one, e.g. `Vector([256, 64])` for a lattice of 256 rows of 64
+    /// particles. Every per-particle buffer then holds the product of the
+    /// extents, in the row-major order of [`crate::indexing::index_in`].
+    pub fn shape(mut self, shape: Vector<usize, DIMS>) -> Self {
+        self.shape.replace(shape);
+        self
+    }
+
+    /// Coupling spring constant, the same along every axis.
     pub fn stiffness(mut self, stiffness: T) -> Self {
+        self.stiffness.replace(Vector::broadcast(stiffness));
+        self
+    }
+
+    /// Coupling spring constant of the bonds along each axis, for
+    /// anisotropic lattices.
+    pub fn stiffness_axis(mut self, stiffness: Vector<T, DIMS>) -> Self {
         self.stiffness.replace(stiffness);
         self
     }
 
+    /// Natural length of a coupling bond as a multiple of its length in the
+    /// undeformed lattice, whose spacing is one. The separation of a bond is
+    /// its lattice offset plus the difference of the two particles'
+    /// positions, and a bond of separation `d` and lattice length `l` pulls
+    /// its ends together with force `stiffness * (|d| - rest_length * l)`
+    /// along `d`. One relaxes the undeformed lattice, smaller values put it
+    /// under tension. The default of zero keeps the coupling linear in the
+    /// difference of the positions.
+    pub fn rest_length(mut self, rest_length: T) -> Self {
+        self.rest_length.replace(rest_length);
+        self
+    }
+
+    /// Adds an anharmonic term `beta * extension³`, taken component-wise, to
+    /// the force of every coupling bond, as in the FPUT-β model.
+    pub fn cubic_coupling(mut self, beta: T) -> Self {
+        self.cubic_coupling.replace(beta);
+        self
+    }
+
+    /// Which particles the coupling bonds connect. See [`Connectivity`].
+    pub fn connectivity(mut self, connectivity: Connectivity) -> Self {
+        self.connectivity.replace(connectivity);
+        self
+    }
+
+    /// Spring constant of the diagonal bonds, if the connectivity has any.
+    pub fn diagonal_stiffness(mut self, diagonal_stiffness: T) -> Self {
+        self.diagonal_stiffness.replace(diagonal_stiffness);
+        self
+    }
+
+    /// Bonds every particle to all others within Euclidean distance
+    /// `radius`, in lattice units. Bonds that the connectivity does not
+    /// already provide get the spring constant `kernel(distance)`. A radius
+    /// of one adds nothing.
+    pub fn coupling_radius(mut self, radius: usize, kernel: impl Fn(T) -> T + 'static) -> Self {
+        self.coupling_radius.replace((radius, Box::new(kernel)));
+        self
+    }
+
     pub fn origin_stiffness(mut self, origin_stiffness: T) -> Self {
         self.origin_stiffness.replace(origin_stiffness);
         self
     }
 
-    pub fn finish(self) -> Simulation<T, SIZE> {
+    /// Mass of every particle. Individual masses can be changed later with
+    /// [`Simulation::set_mass`].
+    pub fn mass(mut self, mass: T) -> Self {
+        self.mass.replace(mass);
+        self
+    }
+
+    /// Viscous damping coefficient: each particle feels a force of
+    /// `-damping * vel`.
+    pub fn damping(mut self, damping: T) -> Self {
+        self.damping.replace(damping);
+        self
+    }
+
+    /// Uniform acceleration applied to every particle, independent of mass.
+    pub fn gravity(mut self, gravity: Vector<T, DIMS>) -> Self {
+        self.gravity.replace(gravity);
+        self
+    }
+
+    pub fn boundary(mut self, boundary: BoundaryCondition) -> Self {
+        self.boundary.replace(boundary);
+        self
+    }
+
+    pub fn integrator(mut self, integrator: Integrator) -> Self {
+        self.integrator.replace(integrator);
+        self
+    }
+
+    /// Smallest step [`Simulation::update_adaptive`] will shrink to.
+    pub fn min_dt(mut self, min_dt: T) -> Self {
+        self.min_dt.replace(min_dt);
+        self
+    }
+
+    /// Splits the force computation over `threads` scoped standard-library
+    /// threads, each computing a contiguous tile of the particles. Meant for
+    /// builds without the `rayon` feature; the default of one computes
+    /// serially, or with rayon if it is enabled. Threads are spawned anew for
+    /// every force evaluation, so this only pays off on large lattices. The
+    /// results are bit-identical whatever the number of threads.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads.replace(threads);
+        self
+    }
+
+    /// Starts the particles at `positions`, one per particle in row-major
+    /// order, instead of at their origins. The buffer is moved into the
+    /// simulation, not copied.
+    pub fn positions(mut self, positions: Box<[Vector<T, DIMS>]>) -> Self {
+        self.positions.replace(positions);
+        self
+    }
+
+    /// Starts every particle displaced from its initial position by a
+    /// uniform random offset in `[-amplitude, amplitude]` per component.
+    /// Equal seeds give equal offsets.
+    pub fn random_init(mut self, amplitude: T, seed: u64) -> Self {
+        self.random_init.replace((amplitude, seed));
+        self
+    }
+
+    /// Displaces the last component of every particle's initial position,
+    /// `y` in two dimensions, by `amplitude * exp(-r² / (2 sigma²))`, where
+    /// `r` is the distance in lattice spacings from the particle's grid
+    /// index to `center`. `center` need not be a grid point. Applied before
+    /// [`random_init`](Self::random_init).
+    pub fn gaussian_bump(mut self, center: Vector<T, DIMS>, amplitude: T, sigma: T) -> Self {
+        self.gaussian_bump.replace((center, amplitude, sigma));
+        self
+    }
+
+    /// Starts the particles moving with velocities drawn from the
+    /// Maxwell–Boltzmann distribution at `temperature`, in units with
+    /// Boltzmann's constant equal to one, less their mean, so the lattice
+    /// has no net momentum. Particles the boundary holds in place stay at
+    /// rest and are left out of the mean. Equal seeds give equal velocities.
+    /// [`Simulation::reset`] still returns the particles to rest.
+    pub fn thermal_init(mut self, temperature: T, seed: u64) -> Self {
+        self.thermal_init.replace((temperature, seed));
+        self
+    }
+
+    /// A drumhead: unit coupling springs, no origin springs and light
+    /// damping, with the edge of the lattice held fixed. Stable with the
+    /// default integrator at `dt = 0.05`. Other setters may be chained after
+    /// it to adjust the preset.
+    pub fn preset_membrane(self) -> Self {
+        self.stiffness(T::one())
+            .origin_stiffness(T::zero())
+            .damping(T::from(0.01).unwrap())
+            .boundary(BoundaryCondition::Fixed)
+    }
+
+    /// A stiff periodic crystal: strong coupling springs, weak origin
+    /// springs to keep it from drifting and moderate damping. Stable with
+    /// the default integrator at `dt = 0.01`. Other setters may be chained
+    /// after it to adjust the preset.
+    pub fn preset_stiff_crystal(self) -> Self {
+        self.stiffness(T::from(100).unwrap())
+            .origin_stiffness(T::one())
+            .damping(T::from(0.1).unwrap())
+            .boundary(BoundaryCondition::Periodic)
+    }
+
+    /// Like [`SimulationBuilder::try_finish`], but panics if a parameter is
+    /// rejected.
+    pub fn finish(self) -> Simulation<T, SIZE, DIMS> {
+        self.try_finish().unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Builds the simulation, substituting defaults for unset parameters.
+    ///
+    /// Fails if a stiffness, including one returned by the
+    /// [`coupling_radius`](Self::coupling_radius) kernel, the rest length,
+    /// the damping or `min_dt` is negative, if the mass or the
+    /// [`gaussian_bump`](Self::gaussian_bump) width is not positive, if any
+    /// parameter is NaN, if the [`shape`](Self::shape) has more particles
+    /// than a `usize` counts, or if [`positions`](Self::positions) does not
+    /// hold one position per particle.
+    pub fn try_finish(self) -> Result<Simulation<T, SIZE, DIMS>, BuildError> {
         let Self {
+            shape,
             stiffness,
+            rest_length,
+            cubic_coupling,
+            connectivity,
+            diagonal_stiffness,
+            coupling_radius,
             origin_stiffness,
+            mass,
+            damping,
+            gravity,
+            boundary,
+            integrator,
+            min_dt,
+            threads,
+            positions,
+            random_init,
+            gaussian_bump,
+            thermal_init,
         } = self;
-        let stiffness = stiffness.unwrap_or(T::one());
+        let stiffness = stiffness.unwrap_or(Vector::broadcast(T::one()));
+        let rest_length = rest_length.unwrap_or(T::zero());
+        let cubic_coupling = cubic_coupling.unwrap_or(T::zero());
+        let connectivity = connectivity.unwrap_or_default();
+        let diagonal_stiffness = diagonal_stiffness.unwrap_or(T::one());
+        let (coupling_radius, long_range_stiffness) = match coupling_radius {
+            Some((radius, kernel)) => {
+                let stiffness = connectivity
+                    .long_range_stencil::<DIMS>(radius)
+                    .into_iter()
+                    .map(|[up, _]| kernel(T::from((up * up).sum()).unwrap().sqrt()))
+                    .collect();
+                (radius, stiffness)
+            }
+            None => (1, Box::default()),
+        };
         let origin_stiffness = origin_stiffness.unwrap_or(T::one());
+        let mass = mass.unwrap_or(T::one());
+        let damping = damping.unwrap_or(T::zero());
+        let gravity = gravity.unwrap_or(Vector::zero());
+        let boundary = boundary.unwrap_or_default();
+        let integrator = integrator.unwrap_or_default();
+        let min_dt = min_dt.unwrap_or(T::epsilon());
+        let threads = threads.unwrap_or(1);
+
+        let not_nan = |name, value: T| match value.is_nan() {
+            true => Err(BuildError::NotANumber(name)),
+            false => Ok(()),
+        };
+        let non_negative = |name, value: T| match value < T::zero() {
+            true => Err(BuildError::Negative(name)),
+            false => not_nan(name, value),
+        };
+        stiffness
+            .iter()
+            .try_for_each(|k| non_negative("stiffness", k))?;
+        non_negative("diagonal stiffness", diagonal_stiffness)?;
+        long_range_stiffness
+            .iter()
+            .try_for_each(|&k| non_negative("long-range stiffness", k))?;
+        non_negative("origin stiffness", origin_stiffness)?;
+        non_negative("rest length", rest_length)?;
+        not_nan("cubic coupling", cubic_coupling)?;
+        not_nan("mass", mass)?;
+        if mass <= T::zero() {
+            return Err(BuildError::NonPositiveMass);
+        }
+        non_negative("damping", damping)?;
+        gravity.iter().try_for_each(|g| not_nan("gravity", g))?;
+        non_negative("min_dt", min_dt)?;
+        if let Some((amplitude, _)) = random_init {
+            not_nan("random init amplitude", amplitude)?;
+        }
+        if let Some((center, amplitude, sigma)) = gaussian_bump {
+            center
+                .iter()
+                .try_for_each(|c| not_nan("gaussian bump center", c))?;
+            not_nan("gaussian bump amplitude", amplitude)?;
+            not_nan("gaussian bump width", sigma)?;
+            if sigma <= T::zero() {
+                return Err(BuildError::NonPositiveWidth);
+            }
+        }
+        if let Some((temperature, _)) = thermal_init {
+            non_negative("temperature", temperature)?;
+        }
+        let shape = shape.unwrap_or(Vector::broadcast(SIZE));
+        let len = shape_len(shape).ok_or(BuildError::TooManyParticles)?;
+        let masses = vec![mass; len].into_boxed_slice();
 
-        let pos = bytemuck::zeroed_box();
-        let vel = bytemuck::zeroed_box();
-        let acc = bytemuck::zeroed_box();
-        let tmp_acc = bytemuck::zeroed_box();
+        let mut pos = match positions {
+            Some(positions) if positions.len() != len => {
+                return Err(LengthMismatch {
+                    expected: len,
+                    actual: positions.len(),
+                }
+                .into())
+            }
+            Some(positions) => positions,
+            None => zeroed_buffer(len),
+        };
+        if let Some((center, amplitude, sigma)) = gaussian_bump {
+            let spread = (T::one() + T::one()) * sigma * sigma;
+            for (k, pos) in pos.iter_mut().enumerate() {
+                let here = deindex_in(shape, k).unwrap().map(|i| T::from(i).unwrap());
+                pos[DIMS - 1] += amplitude * (-(here - center).norm_squared() / spread).exp();
+            }
+        }
+        if let Some((amplitude, seed)) = random_init {
+            let mut rng = StdRng::seed_from_u64(seed);
+            for pos in pos.iter_mut() {
+                *pos += Vector(std::array::from_fn(|_| {
+                    amplitude * T::from(rng.random_range(-1.0..=1.0)).unwrap()
+                }));
+            }
+        }
+        let mut vel = zeroed_buffer(len);
+        if let Some((temperature, seed)) = thermal_init {
+            let mut rng = StdRng::seed_from_u64(seed);
+            // Every mass is the same, so zero momentum is zero mean velocity.
+            let sigma = (temperature / mass).sqrt();
+            let free: Vec<_> = (0..vel.len())
+                .filter(|&k| !boundary.is_clamped(shape, deindex_in(shape, k).unwrap()))
+                .collect();
+            let mut mean = Vector::zero();
+            for &k in &free {
+                vel[k] = Vector(std::array::from_fn(|_| sigma * gaussian::<T>(&mut rng)));
+                mean += vel[k];
+            }
+            if !free.is_empty() {
+                let mean = mean / T::from(free.len()).unwrap();
+                for &k in &free {
+                    vel[k] -= mean;
+                }
+            }
+        }
+        let acc = zeroed_buffer(len);
+        let tmp_acc = zeroed_buffer(len);
+        let pinned = vec![false; len].into_boxed_slice();
 
-        Simulation {
+        let mut simulation = Simulation {
+            force_field: ForceField::none(),
+            neighbors: Neighbors::new(shape, boundary, connectivity, coupling_radius),
+            time: T::zero(),
+            steps: 0,
+            integrator,
+            min_dt,
+            threads,
+            last_adaptive_dt: None,
+            acc_stale: false,
             tmp_acc,
+            tmp_pos: zeroed_buffer(len),
+            tmp_vel: zeroed_buffer(len),
+            rk4_sums: Vec::new(),
+            initial_pos: pos.clone(),
+            langevin: None,
+            history: None,
+            driven: None,
             state: SimulationState {
+                shape,
                 pos,
                 vel,
                 acc,
+                pinned,
                 stiffness,
+                rest_length,
+                cubic_coupling,
+                connectivity,
+                diagonal_stiffness,
+                coupling_radius,
+                long_range_stiffness,
+                bond_stiffness: None,
                 origin_stiffness,
+                mass: masses,
+                damping,
+                gravity,
+                boundary,
             },
-        }
+        };
+        simulation.enforce_boundary();
+        simulation.refresh_acc();
+        Ok(simulation)
     }
 }
 
-fn filter_indices<const SIZE: usize, const DIMS: usize>(
-    indices: Vector<isize, DIMS>,
-) -> Option<Vector<usize, DIMS>> {
-    if indices.map(|i| (0..SIZE as isize).contains(&i)).all() {
-        Some(indices.map(|i| i as usize))
-    } else {
-        None
-    }
-}
+/// Fewest particles handed to one rayon task; smaller lattices are not worth
+/// splitting.
+#[cfg(feature = "rayon")]
+const PARALLEL_CHUNK: usize = 1024;
 
-fn index<const SIZE: usize, const DIMS: usize>(indices: Vector<isize, DIMS>) -> Option<usize> {
-    if let Some(indices) = filter_indices::<SIZE, DIMS>(indices) {
-        let data = Vector::from_idx(|i| SIZE.pow((DIMS - 1 - i) as u32));
-        Some((indices * data).sum())
-    } else {
-        None
-    }
+fn zeroed_buffer<T: Float, const DIMS: usize>(len: usize) -> Box<[Vector<T, DIMS>]> {
+    vec![Vector::zero(); len].into_boxed_slice()
 }
 
-fn deindex<const SIZE: usize, const DIMS: usize>(k: isize) -> Option<Vector<usize, DIMS>> {
-    let range = 0..(SIZE as isize).pow(DIMS as u32);
-    if range.contains(&k) {
-        let k = Vector::broadcast(k as usize);
-        let data = Vector::from_idx(|i| SIZE.pow((DIMS - 1 - i) as u32));
-        Some((k / data) % Vector::broadcast(SIZE))
-    } else {
-        None
+/// `buffer[n]` for the flat offset `n` of a neighbor taken from
+/// [`Neighbors`], without the bounds check under the `unsafe_fast` feature.
+#[inline(always)]
+fn neighbor_at<X>(buffer: &[X], n: usize) -> &X {
+    debug_assert!(n < buffer.len());
+    #[cfg(feature = "unsafe_fast")]
+    // SAFETY: `Neighbors::new` only stores offsets of grid indices that
+    // `BoundaryCondition::resolve` returned, which lie inside the grid, so
+    // `n` is less than the particle count. Every buffer indexed with it
+    // holds one element per particle: the builder allocates the state's
+    // that way,
+    // `grid_serde` and `set_bond_stiffness_field` reject any other length,
+    // and the integrators' scratch buffers come from `zeroed_buffer`.
+    unsafe {
+        buffer.get_unchecked(n)
     }
+    #[cfg(not(feature = "unsafe_fast"))]
+    &buffer[n]
 }
 
-impl<T: Float, const SIZE: usize> Simulation<T, SIZE> {
-    pub fn build() -> SimulationBuilder<T, SIZE> {
+impl<T: Float, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    pub fn build() -> SimulationBuilder<T, SIZE, DIMS> {
         SimulationBuilder {
+            shape: None,
             stiffness: None,
+            rest_length: None,
+            cubic_coupling: None,
+            connectivity: None,
+            diagonal_stiffness: None,
+            coupling_radius: None,
             origin_stiffness: None,
+            mass: None,
+            damping: None,
+            gravity: None,
+            boundary: None,
+            integrator: None,
+            min_dt: None,
+            threads: None,
+            positions: None,
+            random_init: None,
+            gaussian_bump: None,
+            thermal_init: None,
         }
     }
 
+    /// Advances the simulation by `dt` using the configured [`Integrator`].
     pub fn update(&mut self, dt: T) {
+        if self.acc_stale {
+            // A setter may have given a held particle a velocity.
+            self.enforce_boundary();
+            self.refresh_acc();
+        }
+        match self.integrator {
+            Integrator::Euler => self.step_euler(dt),
+            Integrator::SymplecticEuler => self.step_symplectic_euler(dt),
+            Integrator::VelocityVerlet => self.step_velocity_verlet(dt),
+            Integrator::Leapfrog => self.step_leapfrog(dt),
+            Integrator::RungeKutta4 => self.step_rk4(dt),
+        }
+        self.thermalize(dt);
+        self.enforce_boundary();
+        self.drive_edge();
+        self.steps += 1;
+        self.sample_history();
+    }
+
+    /// Like [`Simulation::update`], but then checks that every position and
+    /// velocity is still finite, returning the first particle for which one
+    /// is not.
+    pub fn update_checked(&mut self, dt: T) -> Result<(), InstabilityError<DIMS>> {
+        self.update(dt);
+        let finite = |v: &Vector<T, DIMS>| v.iter().all(|x| x.is_finite());
+        match (self.state.pos.iter().zip(self.state.vel.iter()))
+            .position(|(pos, vel)| !(finite(pos) && finite(vel)))
+        {
+            Some(k) => Err(InstabilityError {
+                index: deindex_in(self.state.shape, k).unwrap(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Takes `steps` steps of size `dt`.
+    pub fn run(&mut self, steps: usize, dt: T) {
+        for _ in 0..steps {
+            self.update(dt);
+        }
+    }
+
+    /// Like [`Simulation::run`], but calls `on_step` after every step with
+    /// the index of that step and the simulation.
+    pub fn run_with(&mut self, steps: usize, dt: T, mut on_step: impl FnMut(usize, &Self)) {
+        for step in 0..steps {
+            self.update(dt);
+            on_step(step, self);
+        }
+    }
+
+    /// Like [`Simulation::run`], but calls `sink` with the number of steps
+    /// taken so far and the simulation after every `stride` steps and after
+    /// the last one, e.g. to stream frames to disk. A stride of zero is
+    /// treated as one.
+    pub fn run_sampled(
+        &mut self,
+        steps: usize,
+        dt: T,
+        stride: usize,
+        mut sink: impl FnMut(usize, &Self),
+    ) {
+        let stride = stride.max(1);
+        for taken in 1..=steps {
+            self.update(dt);
+            if taken % stride == 0 || taken == steps {
+                sink(taken, self);
+            }
+        }
+    }
+
+    /// Like [`Simulation::run`], but stops with an error after the first
+    /// step that leaves the total energy further than `max_drift` times its
+    /// initial magnitude from its initial value, or not finite. A step that
+    /// is too large for the integrator usually shows up this way well
+    /// before any position overflows. With damping, forcing or a thermostat
+    /// the energy is not conserved to begin with, so `max_drift` has to
+    /// allow for that.
+    pub fn run_guarded(
+        &mut self,
+        steps: usize,
+        dt: T,
+        max_drift: T,
+    ) -> Result<(), EnergyDriftError<T>> {
+        let initial = self.total_energy();
+        let limit = max_drift * initial.abs();
+        for step in 0..steps {
+            self.update(dt);
+            let energy = self.total_energy();
+            let drift = (energy - initial).abs();
+            if drift.is_nan() || drift > limit {
+                return Err(EnergyDriftError { step, energy });
+            }
+        }
+        Ok(())
+    }
+
+    /// Steps with `dt` until the simulated time reaches `t_end`, shortening
+    /// the final step so that it lands exactly on `t_end`. Panics if `dt` is
+    /// not positive, which would never get there.
+    pub fn run_until(&mut self, t_end: T, dt: T) {
+        assert!(dt > T::zero(), "dt must be positive");
+        while self.time < t_end {
+            let remaining = t_end - self.time;
+            if remaining <= dt {
+                self.update(remaining);
+                self.time = t_end;
+            } else {
+                self.update(dt);
+            }
+        }
+    }
+
+    /// Returns the particles to the positions the simulation was built with,
+    /// at rest, and the clock and step counter to zero. Physical parameters,
+    /// masses and pins are kept.
+    pub fn reset(&mut self) {
+        self.state.pos.copy_from_slice(&self.initial_pos);
+        self.state.vel.fill(Vector::zero());
+        self.state.acc.fill(Vector::zero());
+        self.tmp_acc.fill(Vector::zero());
+        self.tmp_pos.fill(Vector::zero());
+        self.tmp_vel.fill(Vector::zero());
+        self.rk4_sums.clear();
+        self.time = T::zero();
+        self.steps = 0;
+        self.last_adaptive_dt = None;
+        self.refresh_acc();
+    }
+
+    /// The physical parameters and particle buffers of the simulation.
+    pub fn state(&self) -> &SimulationState<T, SIZE, DIMS> {
+        &self.state
+    }
+
+    /// Replaces the physical parameters and particle buffers, e.g. to
+    /// restore a snapshot taken with [`Simulation::state`]. Panics if
+    /// `state` has a different [`shape`](Self::shape).
+    pub fn set_state(&mut self, state: SimulationState<T, SIZE, DIMS>) {
+        assert_eq!(state.shape, self.state.shape, "state has a different shape");
+        self.state = state;
+        self.sync_neighbors();
+        self.acc_stale = true;
+    }
+
+    /// Extent of the grid along each axis.
+    pub fn shape(&self) -> Vector<usize, DIMS> {
+        self.state.shape
+    }
+
+    /// Simulated time elapsed since the simulation was built.
+    pub fn time(&self) -> T {
+        self.time
+    }
+
+    /// Number of steps taken since the simulation was built, each call to
+    /// [`Simulation::update`] or [`Simulation::update_adaptive`] counting as
+    /// one.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Spring constant of the bonds between neighboring particles, per axis.
+    pub fn stiffness(&self) -> Vector<T, DIMS> {
+        self.state.stiffness
+    }
+
+    /// Sets the coupling spring constant along every axis to `stiffness`.
+    pub fn set_stiffness(&mut self, stiffness: T) {
+        self.set_stiffness_axis(Vector::broadcast(stiffness));
+    }
+
+    pub fn set_stiffness_axis(&mut self, stiffness: Vector<T, DIMS>) {
+        self.state.stiffness = stiffness;
+        self.acc_stale = true;
+    }
+
+    /// Scales the coupling locally: the bond between two particles is
+    /// multiplied by the mean of their values in `field`, which holds one
+    /// value per particle in row-major order. A field of ones is the uniform
+    /// lattice.
+    pub fn set_bond_stiffness_field(&mut self, field: Box<[T]>) -> Result<(), LengthMismatch> {
+        if field.len() != self.state.pos.len() {
+            return Err(LengthMismatch {
+                expected: self.state.pos.len(),
+                actual: field.len(),
+            });
+        }
+        self.state.bond_stiffness = Some(field);
+
+        self.acc_stale = true;
+        Ok(())
+    }
+
+    /// Spring constant pulling every particle toward its own origin.
+    pub fn origin_stiffness(&self) -> T {
+        self.state.origin_stiffness
+    }
+
+    pub fn set_origin_stiffness(&mut self, origin_stiffness: T) {
+        self.state.origin_stiffness = origin_stiffness;
+        self.acc_stale = true;
+    }
+
+    /// Sets the mass of the particle at `index`.
+    pub fn set_mass(&mut self, index: Vector<usize, DIMS>, m: T) -> Result<(), MassError> {
+        if m.is_nan() || m <= T::zero() {
+            return Err(MassError::NotPositive);
+        }
+        let k = checked_offset_in(self.state.shape, index).ok_or(OutOfBounds)?;
+        self.state.mass[k] = m;
+
+        self.acc_stale = true;
+        Ok(())
+    }
+
+    pub fn position(&self, index: Vector<usize, DIMS>) -> Option<Vector<T, DIMS>> {
+        checked_offset_in(self.state.shape, index).map(|k| self.state.pos[k])
+    }
+
+    pub fn set_position(
+        &mut self,
+        index: Vector<usize, DIMS>,
+        pos: Vector<T, DIMS>,
+    ) -> Result<(), OutOfBounds> {
+        let k = checked_offset_in(self.state.shape, index).ok_or(OutOfBounds)?;
+        self.state.pos[k] = pos;
+
+        self.acc_stale = true;
+        Ok(())
+    }
+
+    pub fn velocity(&self, index: Vector<usize, DIMS>) -> Option<Vector<T, DIMS>> {
+        checked_offset_in(self.state.shape, index).map(|k| self.state.vel[k])
+    }
+
+    pub fn set_velocity(
+        &mut self,
+        index: Vector<usize, DIMS>,
+        vel: Vector<T, DIMS>,
+    ) -> Result<(), OutOfBounds> {
+        let k = checked_offset_in(self.state.shape, index).ok_or(OutOfBounds)?;
+        self.state.vel[k] = vel;
+
+        self.acc_stale = true;
+        Ok(())
+    }
+
+    /// Adds `dv` to the velocity of the particle at `index`.
+    pub fn apply_impulse(
+        &mut self,
+        index: Vector<usize, DIMS>,
+        dv: Vector<T, DIMS>,
+    ) -> Result<(), OutOfBounds> {
+        let k = checked_offset_in(self.state.shape, index).ok_or(OutOfBounds)?;
+        self.state.vel[k] += dv;
+
+        self.acc_stale = true;
+        Ok(())
+    }
+
+    /// Adds `dv` to the velocity of every particle whose index lies in the
+    /// inclusive box from `lo` to `hi`. The box is clipped to the grid, so it
+    /// may extend past its edges.
+    pub fn apply_impulse_region(
+        &mut self,
+        lo: Vector<usize, DIMS>,
+        hi: Vector<usize, DIMS>,
+        dv: Vector<T, DIMS>,
+    ) {
+        for (k, vel) in self.state.vel.iter_mut().enumerate() {
+            let index = deindex_in(self.state.shape, k).unwrap();
+            let inside =
+                index.map2(lo, |i, lo| i >= lo).all() && index.map2(hi, |i, hi| i <= hi).all();
+            if inside {
+                *vel += dv;
+            }
+        }
+
+        self.acc_stale = true;
+    }
+
+    /// Holds the particle at `index` in place: its velocity is zeroed now and
+    /// its acceleration and velocity are kept at zero every step, while it
+    /// still exerts coupling forces on its neighbors.
+    pub fn pin(&mut self, index: Vector<usize, DIMS>) -> Result<(), OutOfBounds> {
+        let k = checked_offset_in(self.state.shape, index).ok_or(OutOfBounds)?;
+        self.state.pinned[k] = true;
+        self.state.vel[k] = Vector::zero();
+
+        self.acc_stale = true;
+        Ok(())
+    }
+
+    /// Releases a particle held by [`Simulation::pin`].
+    pub fn unpin(&mut self, index: Vector<usize, DIMS>) -> Result<(), OutOfBounds> {
+        let k = checked_offset_in(self.state.shape, index).ok_or(OutOfBounds)?;
+        self.state.pinned[k] = false;
+
+        self.acc_stale = true;
+        Ok(())
+    }
+
+    /// Iterates over `(index, position, velocity)` of every particle in
+    /// row-major order.
+    pub fn particles(
+        &self,
+    ) -> impl Iterator<Item = (Vector<usize, DIMS>, Vector<T, DIMS>, Vector<T, DIMS>)> + '_ {
+        let SimulationState {
+            shape, pos, vel, ..
+        } = &self.state;
+        pos.iter()
+            .zip(vel.iter())
+            .enumerate()
+            .map(|(k, (&pos, &vel))| (deindex_in(*shape, k).unwrap(), pos, vel))
+    }
+
+    /// Like [`Simulation::particles`], but yields mutable references to the
+    /// position and velocity of every particle.
+    pub fn particles_mut(
+        &mut self,
+    ) -> impl Iterator<
+        Item = (
+            Vector<usize, DIMS>,
+            &mut Vector<T, DIMS>,
+            &mut Vector<T, DIMS>,
+        ),
+    > + '_ {
+        self.acc_stale = true;
+
+        let SimulationState {
+            shape, pos, vel, ..
+        } = &mut self.state;
+        let shape = *shape;
+        pos.iter_mut()
+            .zip(vel.iter_mut())
+            .enumerate()
+            .map(move |(k, (pos, vel))| (deindex_in(shape, k).unwrap(), pos, vel))
+    }
+
+    /// The positions as raw bytes, e.g. for uploading to a GPU without a
+    /// copy. Particles come in row-major order, each as its `DIMS`
+    /// components in axis order, each component a `T` in native byte order,
+    /// with no padding: `DIMS * size_of::<T>()` bytes per particle.
+    pub fn positions_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.state.pos)
+    }
+
+    /// The velocities as raw bytes, laid out like
+    /// [`Simulation::positions_bytes`].
+    pub fn velocities_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.state.vel)
+    }
+
+    /// The positions in row-major particle order, borrowed for bindings that
+    /// hand out the buffer itself.
+    #[cfg(feature = "ffi")]
+    pub(crate) fn position_buffer(&self) -> &[Vector<T, DIMS>] {
+        &self.state.pos
+    }
+
+    /// Recomputes the accelerations in [`Simulation::state`] from the current
+    /// positions and velocities. [`Simulation::update`] does this itself when
+    /// they were modified outside of stepping, so calling it is only needed
+    /// to read up-to-date accelerations in between steps.
+    pub fn refresh_acc(&mut self) {
         self.compute_acc();
         std::mem::swap(&mut self.tmp_acc, &mut self.state.acc);
+        self.acc_stale = false;
     }
 
     fn compute_acc(&mut self) {
-        let SimulationState {
-            stiffness,
-            origin_stiffness,
+        let Self {
+            state,
+            force_field,
+            neighbors,
+            threads,
+            time,
+            tmp_acc,
+            ..
+        } = self;
+        state.accelerations(
+            force_field,
+            neighbors,
+            *threads,
+            *time,
+            &state.pos,
+            &state.vel,
+            tmp_acc,
+        );
+    }
+}
+
+impl<T: Float, const SIZE: usize> Simulation<T, SIZE, 1> {
+    /// Starts building a chain of `SIZE` particles in one dimension, each
+    /// bonded to the particles before and after it. The same as
+    /// [`Simulation::build`] with `DIMS` fixed to 1.
+    pub fn chain() -> SimulationBuilder<T, SIZE, 1> {
+        Self::build()
+    }
+}
+
+impl<T: Float, const SIZE: usize> Simulation<T, SIZE, 2> {
+    /// Starts building a `SIZE` by `SIZE` grid in two dimensions. The same
+    /// as [`Simulation::build`] with `DIMS` fixed to 2.
+    pub fn grid() -> SimulationBuilder<T, SIZE, 2> {
+        Self::build()
+    }
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> SimulationState<T, SIZE, DIMS> {
+    /// A state owning the given position, velocity and acceleration buffers,
+    /// each one vector per particle in row-major order, with the default
+    /// parameters of [`SimulationBuilder`]. Install it with
+    /// [`Simulation::set_state`]. Fails if a buffer does not have
+    /// `SIZE.pow(DIMS)` elements, which no buffer has if that overflows a
+    /// `usize`.
+    pub fn from_raw_parts(
+        pos: Box<[Vector<T, DIMS>]>,
+        vel: Box<[Vector<T, DIMS>]>,
+        acc: Box<[Vector<T, DIMS>]>,
+    ) -> Result<Self, LengthMismatch> {
+        let shape = Vector::broadcast(SIZE);
+        let len = shape_len(shape);
+        for buffer in [&pos, &vel, &acc] {
+            if len != Some(buffer.len()) {
+                return Err(LengthMismatch {
+                    expected: len.unwrap_or(usize::MAX),
+                    actual: buffer.len(),
+                });
+            }
+        }
+        Ok(Self::with_default_parameters(shape, pos, vel, acc))
+    }
+
+    /// A state owning buffers of one vector per particle of `shape`, with
+    /// the parameters [`SimulationBuilder::try_finish`] substitutes when
+    /// none are set.
+    fn with_default_parameters(
+        shape: Vector<usize, DIMS>,
+        pos: Box<[Vector<T, DIMS>]>,
+        vel: Box<[Vector<T, DIMS>]>,
+        acc: Box<[Vector<T, DIMS>]>,
+    ) -> Self {
+        let len = pos.len();
+        SimulationState {
+            shape,
             pos,
-            vel: _,
-            acc: _,
-        } = &mut self.state;
-        let tmp_acc = &mut self.tmp_acc;
-
-        for i in 0..SIZE {
-            for j in 0..SIZE {
-                let indices = Vector([i as isize, j as isize]);
-
-                let position_here = pos[i][j];
-                let origin_acc = -position_here.map(|i| i * *origin_stiffness);
-                let mut coupled_acc: Vector<T, { crate::DIMS }> = Vector::zero();
-
-                for indices in STENCIL
-                    .map(|[stencil_up, stencil_down]| {
-                        let indices_up = indices + stencil_up;
-                        let indices_down = indices + stencil_down;
-
-                        [
-                            filter_indices::<SIZE, { crate::DIMS }>(indices_up),
-                            filter_indices::<SIZE, { crate::DIMS }>(indices_down),
-                        ]
-                    })
-                    .into_iter()
-                    .flatten()
-                    .flatten()
-                {
-                    let position_stencil = pos[indices[0]][indices[1]];
-                    coupled_acc = coupled_acc - position_stencil.map(|i| i * *stiffness);
+            vel,
+            acc,
+            pinned: vec![false; len].into_boxed_slice(),
+            stiffness: Vector::broadcast(T::one()),
+            rest_length: T::zero(),
+            cubic_coupling: T::zero(),
+            connectivity: Connectivity::default(),
+            diagonal_stiffness: T::one(),
+            coupling_radius: 1,
+            long_range_stiffness: Box::default(),
+            bond_stiffness: None,
+            origin_stiffness: T::one(),
+            mass: vec![T::one(); len].into_boxed_slice(),
+            damping: T::zero(),
+            gravity: Vector::zero(),
+            boundary: BoundaryCondition::default(),
+        }
+    }
+
+    /// Gives up the position, velocity and acceleration buffers, in that
+    /// order, dropping the parameters. The inverse of
+    /// [`SimulationState::from_raw_parts`].
+    #[allow(clippy::type_complexity)]
+    pub fn into_raw_parts(
+        self,
+    ) -> (
+        Box<[Vector<T, DIMS>]>,
+        Box<[Vector<T, DIMS>]>,
+        Box<[Vector<T, DIMS>]>,
+    ) {
+        (self.pos, self.vel, self.acc)
+    }
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> SimulationState<T, SIZE, DIMS> {
+    /// Computes the acceleration of every particle for the lattice
+    /// configuration `pos`/`vel` at `time` into `acc`, using the parameters
+    /// of `self`. With the `rayon` feature, particles are computed in
+    /// parallel; each one only reads `pos`/`vel`, so the result is identical
+    /// to the serial loop. With more than one of `threads`, the particles are
+    /// instead split into that many contiguous tiles, each computed on its
+    /// own scoped thread. With the `simd` feature, `f32` and `f64` lattices
+    /// go through the kernel in [`simd`] instead.
+    ///
+    /// Every path writes each particle's acceleration from the same
+    /// arithmetic in the same order, so the result does not depend on how
+    /// the particles are split across threads. Keep it that way: a reduction
+    /// over particles, such as an energy sum, has to be done in a fixed
+    /// order rather than combined from per-thread partial sums.
+    #[allow(clippy::too_many_arguments)]
+    fn accelerations(
+        &self,
+        force_field: &ForceField<T, DIMS>,
+        neighbors: &Neighbors,
+        threads: usize,
+        time: T,
+        pos: &[Vector<T, DIMS>],
+        vel: &[Vector<T, DIMS>],
+        acc: &mut [Vector<T, DIMS>],
+    ) {
+        #[cfg(feature = "simd")]
+        if self.simd_accelerations(force_field, time, pos, vel, acc) {
+            return;
+        }
+
+        if threads > 1 {
+            let tile = acc.len().div_ceil(threads).max(1);
+            std::thread::scope(|scope| {
+                for (t, acc) in acc.chunks_mut(tile).enumerate() {
+                    scope.spawn(move || {
+                        for (i, acc) in acc.iter_mut().enumerate() {
+                            let k = t * tile + i;
+                            *acc = self.acceleration(force_field, neighbors, time, pos, vel, k);
+                        }
+                    });
                 }
+            });
+            return;
+        }
+
+        #[cfg(feature = "rayon")]
+        acc.par_iter_mut()
+            .with_min_len(PARALLEL_CHUNK)
+            .enumerate()
+            .for_each(|(k, acc)| {
+                *acc = self.acceleration(force_field, neighbors, time, pos, vel, k);
+            });
+
+        #[cfg(not(feature = "rayon"))]
+        for (k, acc) in acc.iter_mut().enumerate() {
+            *acc = self.acceleration(force_field, neighbors, time, pos, vel, k);
+        }
+    }
+
+    /// The acceleration of the particle at flat offset `k`.
+    fn acceleration(
+        &self,
+        force_field: &ForceField<T, DIMS>,
+        neighbors: &Neighbors,
+        time: T,
+        pos: &[Vector<T, DIMS>],
+        vel: &[Vector<T, DIMS>],
+        k: usize,
+    ) -> Vector<T, DIMS> {
+        let Self {
+            origin_stiffness,
+            mass,
+            damping,
+            gravity,
+            boundary,
+            ..
+        } = self;
+
+        let here = deindex_in(self.shape, k).unwrap();
+        if self.pinned[k] || boundary.is_clamped(self.shape, here) {
+            return Vector::zero();
+        }
+
+        let position_here = pos[k];
+        let origin_force = -(position_here * *origin_stiffness);
+        let damping_force = -(vel[k] * *damping);
+        let mut coupled_force: Vector<T, DIMS> = Vector::zero();
 
-                tmp_acc[i][j] = origin_acc + coupled_acc;
+        let mut couple = |n, kind, up| {
+            let mut bond_force = self.bond_force(*neighbor_at(pos, n) - position_here, up, kind);
+            if let Some(field) = &self.bond_stiffness {
+                let scale = (field[k] + *neighbor_at(field, n)) / (T::one() + T::one());
+                bond_force *= scale;
             }
+            coupled_force += bond_force;
+        };
+        // Only a rest length needs the lattice offsets.
+        if self.rest_length.is_zero() {
+            for (n, kind) in neighbors.bonds(k) {
+                couple(n, kind, Vector::zero());
+            }
+        } else {
+            for (n, kind, up) in neighbors.bonds_with_offsets::<DIMS>(k) {
+                couple(n, kind, up);
+            }
+        }
+
+        let mut force = origin_force + damping_force + coupled_force;
+        if let Some(external) = force_field.force(here, time) {
+            force += external;
         }
+        force / mass[k] + *gravity
+    }
+
+    /// Spring constant of a bond of the given kind, as tagged by
+    /// [`Connectivity::bond_stencil`].
+    #[inline]
+    fn spring_constant(&self, kind: usize) -> T {
+        match kind.cmp(&DIMS) {
+            std::cmp::Ordering::Less => self.stiffness[kind],
+            std::cmp::Ordering::Equal => self.diagonal_stiffness,
+            std::cmp::Ordering::Greater => self.long_range_stiffness[kind - DIMS - 1],
+        }
+    }
+
+    /// The force a bond of the given kind exerts on one end, given the
+    /// position of the other end relative to it and the lattice offset `up`
+    /// from the one end to the other, which only a rest length uses.
+    #[inline]
+    fn bond_force(
+        &self,
+        extension: Vector<T, DIMS>,
+        up: Vector<isize, DIMS>,
+        kind: usize,
+    ) -> Vector<T, DIMS> {
+        let stiffness = self.spring_constant(kind);
+        let mut force = if self.rest_length.is_zero() {
+            extension * stiffness
+        } else {
+            let lattice = up.map(|i| T::from(i).unwrap());
+            let separation = lattice + extension;
+            let stretch = separation.norm() - self.rest_length * lattice.norm();
+            separation.normalize_or_zero() * (stiffness * stretch)
+        };
+        if !self.cubic_coupling.is_zero() {
+            force += extension.map(|i| self.cubic_coupling * i * i * i);
+        }
+        force
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        simulator::{deindex, index},
-        Vector,
+        error::{BuildError, LengthMismatch, MassError, OutOfBounds},
+        indexing::{deindex, deindex_in, index, index_in, offset, offset_in},
+        BoundaryCondition, Connectivity, Simulation, Vector,
     };
 
+    use super::SimulationState;
+
+    #[test]
+    fn test_raw_parts_round_trip() {
+        let mut sim = Simulation::<f64, 4>::build().random_init(1., 8).finish();
+        sim.set_velocity(Vector([0, 3]), Vector([1., 2.])).unwrap();
+        sim.refresh_acc();
+        let state = sim.state().clone();
+
+        let (pos, vel, acc) = state.clone().into_raw_parts();
+        assert_eq!(pos, sim.state.pos);
+        let pos_ptr = pos.as_ptr();
+        let rebuilt = SimulationState::<f64, 4>::from_raw_parts(pos, vel, acc).unwrap();
+        assert_eq!(rebuilt, state);
+        assert_eq!(rebuilt.pos.as_ptr(), pos_ptr);
+
+        let buffer = || vec![Vector::zero(); 16].into_boxed_slice();
+        assert_eq!(
+            SimulationState::<f64, 4>::from_raw_parts(buffer(), buffer(), Box::new([])),
+            Err(LengthMismatch {
+                expected: 16,
+                actual: 0
+            })
+        );
+
+        // `SIZE.pow(DIMS)` overflows, so no buffer has that many particles.
+        let empty = || Box::new([]);
+        assert_eq!(
+            SimulationState::<f64, { 1 << 33 }>::from_raw_parts(empty(), empty(), empty()),
+            Err(LengthMismatch {
+                expected: usize::MAX,
+                actual: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_raw_bytes() {
+        let mut sim = Simulation::<f32, 4>::build().random_init(1., 3).finish();
+        sim.set_velocity(Vector([1, 2]), Vector([0.5, -2.]))
+            .unwrap();
+
+        let bytes = sim.positions_bytes();
+        assert_eq!(bytes.len(), 4 * 4 * 2 * size_of::<f32>());
+        let pos: &[Vector<f32>] = bytemuck::cast_slice(bytes);
+        assert_eq!(pos, &sim.state.pos[..]);
+
+        let vel: Vec<f32> = sim
+            .velocities_bytes()
+            .chunks(size_of::<f32>())
+            .map(|b| f32::from_ne_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(vel[offset::<4, 2>(Vector([1, 2])) * 2..][..2], [0.5, -2.]);
+
+        let sim = Simulation::<f64, 3, 3>::build().finish();
+        assert_eq!(sim.velocities_bytes().len(), 27 * 3 * size_of::<f64>());
+    }
+
+    #[test]
+    fn test_update_moves_toward_origin() {
+        let mut sim = Simulation::<f64, 4>::build()
+            .stiffness(0.)
+            .origin_stiffness(1.)
+            .finish();
+        sim.state.pos[offset::<4, 2>(Vector([1, 2]))] = Vector([1., -0.5]);
+        sim.refresh_acc();
+
+        let initial = sim.state.pos[offset::<4, 2>(Vector([1, 2]))].norm();
+
+        for _ in 0..10 {
+            sim.update(1e-2);
+        }
+
+        assert!(sim.state.pos[offset::<4, 2>(Vector([1, 2]))].norm() < initial);
+        assert!(sim.state.vel[offset::<4, 2>(Vector([1, 2]))].norm() > 0.);
+        assert_eq!(sim.state.pos[0], Vector::zero());
+    }
+
+    /// Integer positions keep every sum exact, so the result must be the same
+    /// bit for bit with and without the `unsafe_fast` feature.
     #[test]
-    fn test_index() {
-        assert_eq!(index::<100, 2>(Vector([2, 10])), Some(210));
-        assert_eq!(index::<10, 2>(Vector([2, 10])), None);
-        assert_eq!(index::<10, 2>(Vector([10, 2])), None);
-        assert_eq!(index::<10, 2>(Vector([9, 9])), Some(99));
-        assert_eq!(index::<10, 2>(Vector([0, 0])), Some(0));
-        assert_eq!(index::<10, 2>(Vector([-1, 2])), None);
-        assert_eq!(index::<10, 2>(Vector([2, -1])), None);
-        assert_eq!(index::<10, 2>(Vector([-1, -1])), None);
+    fn test_accelerations_exact() {
+        for boundary in [BoundaryCondition::Free, BoundaryCondition::Periodic] {
+            let mut sim = Simulation::<f64, 4>::build()
+                .stiffness(1.)
+                .origin_stiffness(0.)
+                .boundary(boundary)
+                .finish();
+            for (indices, pos, _) in sim.particles_mut() {
+                *pos = Vector([(indices[0] * 3 + indices[1]) as f64, indices[1] as f64]);
+            }
+            sim.refresh_acc();
+
+            for k in 0..16 {
+                let here = deindex::<4, 2>(k).unwrap().map(|i| i as isize);
+                let mut expected = Vector::zero();
+                for step in [[1, 0], [-1, 0], [0, 1], [0, -1]] {
+                    let neighbor = (here + Vector(step)).map(|i| match boundary {
+                        BoundaryCondition::Periodic => i.rem_euclid(4),
+                        _ => i,
+                    });
+                    if let Some(n) = index::<4, 2>(neighbor) {
+                        expected = expected + sim.state.pos[n] - sim.state.pos[k];
+                    }
+                }
+                assert_eq!(sim.state.acc[k], expected, "{boundary:?} particle {k}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_rigid_translation_has_no_coupling() {
+        let mut sim = Simulation::<f64, 5>::build()
+            .stiffness(3.)
+            .origin_stiffness(0.)
+            .finish();
+        sim.state.pos.fill(Vector([0.75, -2.5]));
+        sim.refresh_acc();
+
+        for &acc in sim.state.acc.iter() {
+            assert_eq!(acc, Vector::zero());
+        }
+    }
+
+    #[test]
+    fn test_coupling_pulls_toward_neighbor() {
+        let mut sim = Simulation::<f64, 3>::build()
+            .stiffness(2.)
+            .origin_stiffness(0.)
+            .finish();
+        sim.state.pos[offset::<3, 2>(Vector([1, 1]))] = Vector([1., 0.]);
+        sim.refresh_acc();
+
+        assert_eq!(
+            sim.state.acc[offset::<3, 2>(Vector([1, 1]))],
+            Vector([-8., 0.])
+        );
+        assert_eq!(
+            sim.state.acc[offset::<3, 2>(Vector([0, 1]))],
+            Vector([2., 0.])
+        );
+        assert_eq!(sim.state.acc[0], Vector::zero());
+    }
+
+    // The SIMD kernel rounds differently from the scalar path; it has its
+    // own comparison in `simd`.
+    #[cfg(all(feature = "rayon", not(feature = "simd")))]
+    #[test]
+    fn test_parallel_accelerations_match_serial() {
+        let mut sim = Simulation::<f64, 32>::build()
+            .stiffness(1.5)
+            .origin_stiffness(0.25)
+            .damping(0.125)
+            .boundary(crate::BoundaryCondition::Periodic)
+            .finish();
+        for (indices, pos, vel) in sim.particles_mut() {
+            let [i, j] = indices.map(|i| i as f64).0;
+            *pos = Vector([(i * 0.3).sin(), (j * 0.7).cos()]);
+            *vel = Vector([j * 1e-2, -i * 1e-2]);
+        }
+        sim.set_mass(Vector([5, 7]), 3.).unwrap();
+        sim.set_force_field(|index, time| Vector([index[0] as f64 * time, 1.]));
+        sim.run(3, 1e-2);
+        sim.refresh_acc();
+
+        let state = &sim.state;
+        for k in 0..state.acc.len() {
+            let serial = state.acceleration(
+                &sim.force_field,
+                &sim.neighbors,
+                sim.time,
+                &state.pos,
+                &state.vel,
+                k,
+            );
+            assert_eq!(state.acc[k].0.map(f64::to_bits), serial.0.map(f64::to_bits));
+        }
+    }
+
+    #[test]
+    fn test_threaded_accelerations_match_serial() {
+        let run = |threads| {
+            let mut sim = Simulation::<f64, 13>::build()
+                .stiffness(1.5)
+                .connectivity(Connectivity::WithDiagonals)
+                .damping(0.125)
+                .boundary(BoundaryCondition::Periodic)
+                .integrator(crate::Integrator::RungeKutta4)
+                .random_init(0.5, 9)
+                .threads(threads)
+                .finish();
+            sim.set_mass(Vector([5, 7]), 3.).unwrap();
+            sim.pin(Vector([0, 3])).unwrap();
+            sim.set_force_field(|index, time| Vector([index[0] as f64 * time, 1.]));
+            sim.run(5, 1e-2);
+            sim
+        };
+        let serial = run(1);
+        for threads in [0, 2, 3, 4, 7, 169, 200] {
+            let threaded = run(threads);
+            assert_eq!(threaded.state.pos, serial.state.pos, "{threads} threads");
+            assert_eq!(threaded.state.acc, serial.state.acc, "{threads} threads");
+        }
+    }
+
+    #[test]
+    fn test_results_independent_of_thread_count() {
+        let bits = |buffer: &[Vector<f64>]| -> Vec<[u64; 2]> {
+            buffer.iter().map(|v| v.0.map(f64::to_bits)).collect()
+        };
+        for seed in 0..8 {
+            let boundary =
+                [BoundaryCondition::Free, BoundaryCondition::Periodic][seed as usize % 2];
+            let run = |threads| {
+                let mut sim = Simulation::<f64, 16>::build()
+                    .stiffness(1. + seed as f64 / 4.)
+                    .origin_stiffness(0.5)
+                    .damping(0.05)
+                    .boundary(boundary)
+                    .random_init(1., seed)
+                    .thermal_init(0.1, seed + 100)
+                    .threads(threads)
+                    .finish();
+                sim.run(20, 1e-2);
+                sim
+            };
+            let serial = run(1);
+            for threads in [2, 4, 8] {
+                let threaded = run(threads);
+                let (a, b) = (&threaded.state, &serial.state);
+                assert_eq!(bits(&a.pos), bits(&b.pos), "seed {seed}, {threads} threads");
+                assert_eq!(bits(&a.vel), bits(&b.vel), "seed {seed}, {threads} threads");
+                assert_eq!(bits(&a.acc), bits(&b.acc), "seed {seed}, {threads} threads");
+                assert_eq!(
+                    threaded.total_energy().to_bits(),
+                    serial.total_energy().to_bits()
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "half")]
+    fn test_half_precision_tracks_f32() {
+        use half::{bf16, f16};
+
+        fn probe<T: crate::Float>() -> Vec<f32> {
+            let mut sim = Simulation::<T, 16>::build()
+                .stiffness(T::from(0.5).unwrap())
+                .origin_stiffness(T::from(0.25).unwrap())
+                .finish();
+            sim.set_position(Vector([8, 8]), Vector([T::one(), T::zero()]))
+                .unwrap();
+            let dt = T::from(0.05).unwrap();
+            (0..20)
+                .map(|_| {
+                    sim.run(5, dt);
+                    sim.position(Vector([8, 9])).unwrap()[0].to_f32().unwrap()
+                })
+                .collect()
+        }
+
+        let mut large = Simulation::<f16, 64>::build()
+            .random_init(f16::ONE, 1)
+            .finish();
+        large.run(3, f16::from_f32(0.05));
+        assert!(large.max_displacement().1.is_finite());
+
+        let baseline = probe::<f32>();
+        for (name, run, tolerance) in [
+            ("f16", probe::<f16>(), 5e-3),
+            ("bf16", probe::<bf16>(), 3e-2),
+        ] {
+            assert!(run.iter().all(|x| x.is_finite()), "{name}");
+            for (x, y) in run.iter().zip(&baseline) {
+                assert!((x - y).abs() < tolerance, "{name}: {x} vs {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_three_dimensional_lattice() {
+        let mut sim = Simulation::<f32, 4, 3>::build()
+            .stiffness(1.)
+            .origin_stiffness(0.5)
+            .gravity(Vector::zero())
+            .finish();
+        let interior = Vector([1, 2, 1]);
+        sim.set_position(interior, Vector([0.5, 0., -0.25]))
+            .unwrap();
+
+        sim.run(10, 1e-2);
+
+        let pos = sim.position(interior).unwrap();
+        assert!(pos[0] < 0.5 && pos[2] > -0.25);
+        assert!(sim.velocity(interior).unwrap()[0] < 0.);
+        // Face neighbors along both the first and the third axis are pulled
+        // along by the coupling springs.
+        assert!(sim.velocity(Vector([1, 2, 2])).unwrap()[2] < 0.);
+        assert!(sim.velocity(Vector([0, 2, 1])).unwrap()[0] > 0.);
+    }
+
+    #[test]
+    fn test_set_stiffness() {
+        let mut sim = Simulation::<f64, 3>::build()
+            .stiffness(2.)
+            .origin_stiffness(0.)
+            .finish();
+        sim.set_position(Vector([1, 1]), Vector([1., 0.])).unwrap();
+        sim.refresh_acc();
+        let stiff = sim.state.acc[offset::<3, 2>(Vector([0, 1]))].norm();
+
+        sim.set_stiffness(0.5);
+        sim.set_origin_stiffness(0.25);
+        assert_eq!(sim.stiffness(), Vector([0.5, 0.5]));
+        assert_eq!(sim.origin_stiffness(), 0.25);
+        sim.compute_acc();
+        let soft = sim.tmp_acc[offset::<3, 2>(Vector([0, 1]))].norm();
+
+        assert_eq!(stiff, 2.);
+        assert_eq!(soft, 0.5);
+    }
+
+    #[test]
+    fn test_anisotropic_stiffness() {
+        let mut sim = Simulation::<f64, 5>::build()
+            .stiffness_axis(Vector([4., 0.25]))
+            .origin_stiffness(0.)
+            .finish();
+        sim.set_position(Vector([2, 2]), Vector([1., 1.])).unwrap();
+
+        sim.run(10, 1e-2);
+
+        // The first axis is the row index, so [1, 2] and [3, 2] are bonded
+        // to the displaced particle through the stiff springs.
+        let stiff = sim.position(Vector([1, 2])).unwrap();
+        let soft = sim.position(Vector([2, 1])).unwrap();
+        assert!(stiff[0] > 10. * soft[0]);
+        assert_eq!(stiff[0], sim.position(Vector([3, 2])).unwrap()[0]);
+        assert_eq!(soft[0], sim.position(Vector([2, 3])).unwrap()[0]);
+    }
+
+    #[test]
+    fn test_rest_length() {
+        let mut sim = Simulation::<f64, 2, 1>::build()
+            .rest_length(1.)
+            .origin_stiffness(0.)
+            .finish();
+        // The second particle sits one lattice spacing up, so its
+        // displacement adds to a separation of one.
+        let force_on_first = |sim: &mut Simulation<f64, 2, 1>, displacement| {
+            sim.set_position(Vector([1]), Vector([displacement]))
+                .unwrap();
+            sim.refresh_acc();
+            sim.state.acc[0][0]
+        };
+
+        assert_eq!(force_on_first(&mut sim, 0.5), 0.5);
+        assert_eq!(force_on_first(&mut sim, -0.75), -0.75);
+        assert_eq!(force_on_first(&mut sim, 0.), 0.);
+        assert_eq!(force_on_first(&mut sim, -2.5), -0.5);
+    }
+
+    #[test]
+    fn test_rest_length_leaves_uniform_states_unforced() {
+        for boundary in [BoundaryCondition::Periodic, BoundaryCondition::Free] {
+            for connectivity in [Connectivity::AxisNeighbors, Connectivity::WithDiagonals] {
+                let mut sim = Simulation::<f64, 6>::build()
+                    .rest_length(1.)
+                    .origin_stiffness(0.)
+                    .boundary(boundary)
+                    .connectivity(connectivity)
+                    .finish();
+                for index in 0..36 {
+                    let index = deindex::<6, 2>(index).unwrap();
+                    sim.set_position(index, Vector([0.25, -0.5])).unwrap();
+                }
+                sim.refresh_acc();
+
+                for acc in sim.state.acc.iter() {
+                    assert!(acc.norm() < 1e-12, "{boundary:?} {connectivity:?}: {acc:?}");
+                }
+                assert!(sim.potential_energy().abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cubic_coupling_stiffens_large_extensions() {
+        let restoring = |beta, extension| {
+            let mut sim = Simulation::<f64, 2, 1>::build()
+                .cubic_coupling(beta)
+                .origin_stiffness(0.)
+                .finish();
+            sim.set_position(Vector([1]), Vector([extension])).unwrap();
+            sim.refresh_acc();
+            sim.state.acc[0][0]
+        };
+
+        assert_eq!(restoring(0.5, 0.), 0.);
+        assert_eq!(restoring(0.5, 2.), 2. + 0.5 * 8.);
+        assert_eq!(restoring(0.5, -2.), -(2. + 0.5 * 8.));
+        assert!(restoring(0.5, 1e-3) / restoring(0., 1e-3) < 1. + 1e-6);
+        assert!(restoring(0.5, 3.) / restoring(0., 3.) > 5.);
+    }
+
+    #[test]
+    fn test_soft_stripe_slows_waves() {
+        const SIZE: usize = 48;
+        let arrival = |soft: bool| {
+            let mut sim = Simulation::<f64, SIZE, 1>::build()
+                .origin_stiffness(0.)
+                .finish();
+            if soft {
+                let field = (0..SIZE).map(|i| if (12..24).contains(&i) { 0.1 } else { 1. });
+                sim.set_bond_stiffness_field(field.collect()).unwrap();
+            }
+            sim.apply_impulse(Vector([0]), Vector([1.])).unwrap();
+
+            let mut steps = 0;
+            while sim.position(Vector([36])).unwrap()[0] < 1e-3 {
+                sim.update(1e-2);
+                steps += 1;
+            }
+            steps
+        };
+
+        assert!(arrival(true) > arrival(false) * 3 / 2);
+
+        let mut sim = Simulation::<f64, 4>::build().finish();
+        assert_eq!(
+            sim.set_bond_stiffness_field(vec![1.; 15].into_boxed_slice()),
+            Err(LengthMismatch {
+                expected: 16,
+                actual: 15
+            })
+        );
+    }
+
+    #[test]
+    fn test_diagonals_resist_shear() {
+        let restoring = |connectivity| {
+            let mut sim = Simulation::<f64, 3>::build()
+                .connectivity(connectivity)
+                .diagonal_stiffness(0.5)
+                .origin_stiffness(0.)
+                .finish();
+            // Slide the center and its axis neighbors past the corners, which
+            // only strains the diagonal bonds of the center.
+            for index in [[1, 1], [0, 1], [2, 1], [1, 0], [1, 2]] {
+                sim.set_position(Vector(index), Vector([0.25, 0.])).unwrap();
+            }
+            sim.refresh_acc();
+            sim.state.acc[offset::<3, 2>(Vector([1, 1]))]
+        };
+
+        assert_eq!(restoring(Connectivity::AxisNeighbors), Vector::zero());
+        assert_eq!(restoring(Connectivity::WithDiagonals), Vector([-0.5, 0.]));
+        assert_eq!(restoring(Connectivity::Moore), Vector([-0.5, 0.]));
+    }
+
+    #[test]
+    fn test_coupling_radius() {
+        let build = |radius| {
+            let mut sim = Simulation::<f64, 7>::build()
+                .origin_stiffness(0.)
+                .coupling_radius(radius, |distance| if distance < 2. { 0.5 } else { 0.25 })
+                .finish();
+            sim.set_position(Vector([3, 3]), Vector([1., 0.])).unwrap();
+            sim.refresh_acc();
+            sim
+        };
+        let mut nearest = Simulation::<f64, 7>::build().origin_stiffness(0.).finish();
+        nearest
+            .set_position(Vector([3, 3]), Vector([1., 0.]))
+            .unwrap();
+        nearest.refresh_acc();
+        let acc = |sim: &Simulation<f64, 7>, index| sim.state.acc[offset::<7, 2>(Vector(index))];
+
+        assert_eq!(build(1), nearest);
+
+        let far = build(2);
+        assert_eq!(
+            far.neighbors.bonds(offset::<7, 2>(Vector([3, 3]))).count(),
+            12
+        );
+        assert_eq!(acc(&far, [2, 3]), Vector([1., 0.]));
+        assert_eq!(acc(&far, [2, 2]), Vector([0.5, 0.]));
+        assert_eq!(acc(&far, [1, 3]), Vector([0.25, 0.]));
+        assert_eq!(acc(&far, [1, 2]), Vector::zero());
+        assert_eq!(
+            acc(&far, [3, 3]),
+            Vector([-(4. + 4. * 0.5 + 4. * 0.25), 0.])
+        );
+    }
+
+    #[test]
+    fn test_try_finish_rejects_invalid_parameters() {
+        let build = Simulation::<f64, 3>::build;
+        let error = |builder: super::SimulationBuilder<f64, 3>| builder.try_finish().err();
+
+        assert_eq!(
+            error(build().stiffness(-1.)),
+            Some(BuildError::Negative("stiffness"))
+        );
+        assert_eq!(
+            error(build().stiffness_axis(Vector([1., f64::NAN]))),
+            Some(BuildError::NotANumber("stiffness"))
+        );
+        assert_eq!(
+            error(build().diagonal_stiffness(-0.5)),
+            Some(BuildError::Negative("diagonal stiffness"))
+        );
+        assert_eq!(
+            error(build().coupling_radius(2, |distance| 1. - distance)),
+            Some(BuildError::Negative("long-range stiffness"))
+        );
+        assert_eq!(
+            error(build().origin_stiffness(-1.)),
+            Some(BuildError::Negative("origin stiffness"))
+        );
+        assert_eq!(
+            error(build().rest_length(-1.)),
+            Some(BuildError::Negative("rest length"))
+        );
+        assert_eq!(
+            error(build().cubic_coupling(f64::NAN)),
+            Some(BuildError::NotANumber("cubic coupling"))
+        );
+        assert_eq!(error(build().mass(-2.)), Some(BuildError::NonPositiveMass));
+        assert_eq!(error(build().mass(0.)), Some(BuildError::NonPositiveMass));
+        assert_eq!(
+            error(build().mass(f64::NAN)),
+            Some(BuildError::NotANumber("mass"))
+        );
+        assert_eq!(
+            error(build().damping(-0.1)),
+            Some(BuildError::Negative("damping"))
+        );
+        assert_eq!(
+            error(build().gravity(Vector([f64::NAN, 0.]))),
+            Some(BuildError::NotANumber("gravity"))
+        );
+        assert_eq!(
+            error(build().min_dt(-1e-3)),
+            Some(BuildError::Negative("min_dt"))
+        );
+        assert_eq!(
+            error(build().random_init(f64::NAN, 1)),
+            Some(BuildError::NotANumber("random init amplitude"))
+        );
+
+        let sim = build()
+            .stiffness(2.)
+            .cubic_coupling(-0.5)
+            .gravity(Vector([0., -9.8]))
+            .try_finish()
+            .unwrap();
+        assert_eq!(
+            sim,
+            build()
+                .stiffness(2.)
+                .cubic_coupling(-0.5)
+                .gravity(Vector([0., -9.8]))
+                .finish()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "damping must not be negative")]
+    fn test_finish_panics_on_invalid_parameters() {
+        Simulation::<f64, 3>::build().damping(-1.).finish();
+    }
+
+    #[test]
+    fn test_mass_scales_acceleration() {
+        let displaced = |mass| {
+            let mut sim = Simulation::<f64, 3>::build().mass(mass).finish();
+            sim.state.pos[offset::<3, 2>(Vector([1, 1]))] = Vector([1., -0.5]);
+            sim.refresh_acc();
+            sim.state.acc[offset::<3, 2>(Vector([1, 1]))]
+        };
+
+        assert_eq!(displaced(2.), displaced(1.).map(|a| a / 2.));
+    }
+
+    #[test]
+    fn test_damping_dissipates_energy() {
+        let mut sim = Simulation::<f64, 4>::build()
+            .stiffness(2.)
+            .origin_stiffness(0.5)
+            .damping(0.5)
+            .finish();
+        sim.state.pos[offset::<4, 2>(Vector([1, 2]))] = Vector([1., -0.5]);
+        sim.state.vel[offset::<4, 2>(Vector([3, 0]))] = Vector([0.25, 0.5]);
+        sim.refresh_acc();
+
+        let initial = sim.total_energy();
+        let mut previous = initial;
+        for _ in 0..5_000 {
+            sim.update(1e-3);
+            let current = sim.total_energy();
+            assert!(current < previous);
+            previous = current;
+        }
+        assert!(previous < 0.5 * initial);
+    }
+
+    #[test]
+    fn test_gravity_equilibrium() {
+        let mut sim = Simulation::<f64, 1>::build()
+            .origin_stiffness(4.)
+            .damping(2.)
+            .gravity(Vector([0., -9.81]))
+            .finish();
+
+        for _ in 0..20_000 {
+            sim.update(1e-3);
+        }
+
+        let expected = Vector([0., -9.81 / 4.]);
+        assert!((sim.state.pos[0] - expected).norm() < 1e-6);
+        assert!(sim.state.vel[0].norm() < 1e-6);
+    }
+
+    #[test]
+    fn test_initial_positions() {
+        let checkerboard: Box<[_]> = (0..16)
+            .map(|k| {
+                let sign = if (k / 4 + k % 4) % 2 == 0 { 1. } else { -1. };
+                Vector([sign * 0.5, 0.])
+            })
+            .collect();
+        let mut sim = Simulation::<f64, 4>::build()
+            .positions(checkerboard.clone())
+            .finish();
+
+        assert_eq!(sim.state.pos, checkerboard);
+        assert_eq!(sim.position(Vector([0, 0])), Some(Vector([0.5, 0.])));
+        assert_eq!(sim.position(Vector([2, 3])), Some(Vector([-0.5, 0.])));
+        assert!(sim.state.vel.iter().all(|&v| v == Vector::zero()));
+
+        sim.run(10, 1e-2);
+        sim.reset();
+        assert_eq!(sim.state.pos, checkerboard);
+
+        let jittered = Simulation::<f64, 4>::build()
+            .positions(checkerboard.clone())
+            .random_init(0.1, 3)
+            .finish();
+        for (pos, base) in jittered.state.pos.iter().zip(checkerboard.iter()) {
+            assert!((*pos - *base).iter().all(|d| d.abs() <= 0.1));
+        }
+
+        let error = Simulation::<f64, 4>::build()
+            .positions(checkerboard[..15].into())
+            .try_finish()
+            .err();
+        assert_eq!(
+            error,
+            Some(BuildError::LengthMismatch(LengthMismatch {
+                expected: 16,
+                actual: 15
+            }))
+        );
+    }
+
+    #[test]
+    fn test_presets_are_stable() {
+        type Builder = super::SimulationBuilder<f64, 16>;
+        type Preset = fn(Builder) -> Builder;
+        let presets: [(Preset, f64); 2] = [
+            (Builder::preset_membrane, 0.05),
+            (Builder::preset_stiff_crystal, 0.01),
+        ];
+        for (preset, dt) in presets {
+            let mut sim = preset(Simulation::build())
+                .gaussian_bump(Vector([8., 8.]), 0.5, 2.)
+                .random_init(0.05, 1)
+                .finish();
+            assert!(dt < sim.max_stable_dt());
+            let energy = sim.total_energy();
+            for _ in 0..1000 {
+                sim.update_checked(dt).unwrap();
+            }
+            assert!(sim.total_energy() < energy);
+        }
+    }
+
+    #[test]
+    fn test_gaussian_bump() {
+        let sim = Simulation::<f64, 9>::build()
+            .gaussian_bump(Vector([4., 4.]), 0.5, 1.)
+            .finish();
+
+        let (peak, height) = sim
+            .particles()
+            .map(|(index, pos, _)| (index, pos[1]))
+            .fold((Vector([0, 0]), 0.), |a, b| if b.1 > a.1 { b } else { a });
+        assert_eq!(peak, Vector([4, 4]));
+        assert_eq!(height, 0.5);
+        assert!(sim.particles().all(|(_, pos, _)| pos[0] == 0.));
+
+        let one_off = sim.position(Vector([4, 5])).unwrap()[1];
+        assert!((one_off - 0.5 * (-0.5f64).exp()).abs() < 1e-12);
+        for i in 0..9 {
+            for edge in [
+                Vector([0, i]),
+                Vector([8, i]),
+                Vector([i, 0]),
+                Vector([i, 8]),
+            ] {
+                assert!(sim.position(edge).unwrap()[1] < 1e-3);
+            }
+        }
+
+        let error = Simulation::<f64, 9>::build()
+            .gaussian_bump(Vector([4., 4.]), 0.5, 0.)
+            .try_finish()
+            .err();
+        assert_eq!(error, Some(BuildError::NonPositiveWidth));
+    }
+
+    #[test]
+    fn test_thermal_init() {
+        let build = |seed| {
+            Simulation::<f64, 16>::build()
+                .mass(2.)
+                .thermal_init(0.5, seed)
+                .finish()
+        };
+        let sim = build(4);
+        assert!(sim.total_momentum().norm() < 1e-12);
+        assert_eq!(sim.state.vel, build(4).state.vel);
+        assert_ne!(sim.state.vel, build(5).state.vel);
+
+        // Half the temperature for each of the 256 * 2 velocity components,
+        // less the two removed with the mean.
+        let target = (256. * 2. - 2.) * 0.5 / 2.;
+        let kinetic = sim.kinetic_energy();
+        assert!(
+            (kinetic - target).abs() < 0.1 * target,
+            "{kinetic} vs {target}"
+        );
+
+        // The held edge of a fixed lattice stays at rest and out of the mean.
+        let mut fixed = Simulation::<f64, 6>::build()
+            .boundary(BoundaryCondition::Fixed)
+            .thermal_init(1., 3)
+            .finish();
+        assert!(fixed.total_momentum().norm() < 1e-12);
+        fixed.update(1e-2);
+        for k in 0..36 {
+            let here = deindex::<6, 2>(k).unwrap();
+            if BoundaryCondition::Fixed.is_clamped(Vector([6, 6]), here) {
+                assert_eq!(fixed.state.pos[k], Vector::zero(), "{here:?}");
+            }
+        }
+
+        // An empty lattice has no velocities to draw.
+        let empty = Simulation::<f64, 0>::build().thermal_init(1., 0).finish();
+        assert!(empty.state.vel.is_empty());
+        let empty = Simulation::<f64, 4>::build()
+            .shape(Vector([4, 0]))
+            .thermal_init(1., 0)
+            .finish();
+        assert!(empty.state.vel.is_empty());
+
+        let error = Simulation::<f64, 4>::build()
+            .thermal_init(-1., 0)
+            .try_finish()
+            .err();
+        assert_eq!(error, Some(BuildError::Negative("temperature")));
+    }
+
+    #[test]
+    fn test_random_init_is_seeded() {
+        let build = |seed| {
+            Simulation::<f64, 6>::build()
+                .random_init(0.5, seed)
+                .finish()
+        };
+        let (a, b, c) = (build(7), build(7), build(8));
+
+        assert_eq!(a.state().pos, b.state().pos);
+        assert_ne!(a.state().pos, c.state().pos);
+        assert!(a
+            .particles()
+            .all(|(_, pos, _)| pos.map(|x| x.abs() <= 0.5).all()));
+        assert!(a.particles().any(|(_, pos, _)| pos != Vector::zero()));
+    }
+
+    #[test]
+    fn test_set_mass() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+
+        assert_eq!(sim.set_mass(Vector([1, 2]), 4.), Ok(()));
+        assert_eq!(sim.state.mass[offset::<3, 2>(Vector([1, 2]))], 4.);
+        assert_eq!(sim.state.mass[offset::<3, 2>(Vector([2, 1]))], 1.);
+
+        assert_eq!(
+            sim.set_mass(Vector([3, 0]), 1.),
+            Err(MassError::OutOfBounds(OutOfBounds))
+        );
+        assert_eq!(
+            sim.set_mass(Vector([0, 0]), 0.),
+            Err(MassError::NotPositive)
+        );
+        assert_eq!(
+            sim.set_mass(Vector([0, 0]), -1.),
+            Err(MassError::NotPositive)
+        );
+        assert_eq!(
+            sim.set_mass(Vector([0, 0]), f64::NAN),
+            Err(MassError::NotPositive)
+        );
+    }
+
+    #[test]
+    fn test_heavy_particle_accelerates_less() {
+        let mut sim = Simulation::<f64, 3>::build()
+            .stiffness(0.)
+            .gravity(Vector::zero())
+            .finish();
+        sim.state.pos[0] = Vector([1., 0.]);
+        sim.state.pos[offset::<3, 2>(Vector([2, 2]))] = Vector([1., 0.]);
+        sim.set_mass(Vector([2, 2]), 10.).unwrap();
+        sim.refresh_acc();
+
+        assert_eq!(sim.state.acc[0], Vector([-1., 0.]));
+        assert_eq!(
+            sim.state.acc[offset::<3, 2>(Vector([2, 2]))],
+            Vector([-0.1, 0.])
+        );
+    }
+
+    #[test]
+    fn test_position_velocity_round_trip() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+
+        sim.set_position(Vector([1, 2]), Vector([1., -0.5]))
+            .unwrap();
+        sim.set_velocity(Vector([2, 0]), Vector([0.25, 4.]))
+            .unwrap();
+
+        assert_eq!(sim.position(Vector([1, 2])), Some(Vector([1., -0.5])));
+        assert_eq!(sim.velocity(Vector([2, 0])), Some(Vector([0.25, 4.])));
+        assert_eq!(sim.position(Vector([2, 0])), Some(Vector::zero()));
+        assert_eq!(sim.velocity(Vector([1, 2])), Some(Vector::zero()));
+    }
+
+    #[test]
+    fn test_position_velocity_out_of_bounds() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+
+        assert_eq!(sim.position(Vector([3, 0])), None);
+        assert_eq!(sim.position(Vector([0, 3])), None);
+        assert_eq!(sim.velocity(Vector([3, 3])), None);
+        assert_eq!(
+            sim.set_position(Vector([0, 3]), Vector::zero()),
+            Err(OutOfBounds)
+        );
+        assert_eq!(
+            sim.set_velocity(Vector([3, 0]), Vector::zero()),
+            Err(OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn test_impulse_propagates_to_neighbors() {
+        let mut sim = Simulation::<f64, 5>::build()
+            .stiffness(2.)
+            .origin_stiffness(0.)
+            .finish();
+        sim.apply_impulse(Vector([2, 2]), Vector([1., 0.])).unwrap();
+        sim.apply_impulse(Vector([2, 2]), Vector([1., 0.5]))
+            .unwrap();
+        assert_eq!(sim.velocity(Vector([2, 2])), Some(Vector([2., 0.5])));
+
+        sim.run(10, 1e-2);
+
+        for neighbor in [[1, 2], [3, 2], [2, 1], [2, 3]] {
+            assert!(sim.velocity(Vector(neighbor)).unwrap()[0] > 0.);
+        }
+        assert!(sim.velocity(Vector([2, 2])).unwrap()[0] < 2.);
+        assert_eq!(
+            sim.apply_impulse(Vector([5, 0]), Vector::zero()),
+            Err(OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn test_chain_pulse_travels_at_finite_speed() {
+        // With unit stiffness and mass the sound speed is one site per unit
+        // time.
+        let mut sim = Simulation::<f64, 64, 1>::chain()
+            .stiffness(1.)
+            .origin_stiffness(0.)
+            .finish();
+        sim.set_position(Vector([0]), Vector([1.])).unwrap();
+
+        sim.run_until(20., 1e-2);
+
+        let displacement = |i| sim.position(Vector([i])).unwrap()[0].abs();
+        assert!(displacement(10) > 1e-2);
+        assert!(displacement(40) < 1e-6);
+        assert!(displacement(63) < 1e-12);
+
+        let grid = Simulation::<f64, 3>::grid().finish();
+        assert_eq!(grid.state().pos.len(), 9);
+    }
+
+    #[test]
+    fn test_impulse_region() {
+        let dv = Vector([1., -1.]);
+        let mut whole = Simulation::<f64, 4>::build().finish();
+        whole.apply_impulse_region(Vector([0, 0]), Vector([10, 10]), dv);
+        assert!(whole.particles().all(|(_, _, vel)| vel == dv));
+
+        let mut sub = Simulation::<f64, 4>::build().finish();
+        sub.apply_impulse_region(Vector([1, 2]), Vector([2, 7]), dv);
+        for (Vector([i, j]), _, vel) in sub.particles() {
+            let inside = (1..=2).contains(&i) && j >= 2;
+            assert_eq!(vel, if inside { dv } else { Vector::zero() });
+        }
+    }
+
+    #[test]
+    fn test_pinned_particle_stays_put() {
+        let mut sim = Simulation::<f64, 5>::build()
+            .stiffness(2.)
+            .origin_stiffness(0.)
+            .damping(1.)
+            .finish();
+        let handle = Vector([2, 2]);
+        sim.set_position(handle, Vector([1., 0.5])).unwrap();
+        sim.set_velocity(handle, Vector([3., 0.])).unwrap();
+        sim.pin(handle).unwrap();
+
+        sim.run(2000, 1e-2);
+
+        assert_eq!(sim.position(handle), Some(Vector([1., 0.5])));
+        assert_eq!(sim.velocity(handle), Some(Vector::zero()));
+        for (_, pos, _) in sim.particles() {
+            assert!((pos - Vector([1., 0.5])).norm() < 1e-3);
+        }
+
+        sim.unpin(handle).unwrap();
+        sim.set_velocity(handle, Vector([1., 0.])).unwrap();
+        sim.update(1e-2);
+        assert_ne!(sim.position(handle), Some(Vector([1., 0.5])));
+        assert_eq!(sim.pin(Vector([0, 5])), Err(OutOfBounds));
+    }
+
+    #[test]
+    fn test_held_particles_ignore_velocity_setters() {
+        let mut sim = Simulation::<f64, 5>::build()
+            .boundary(BoundaryCondition::Fixed)
+            .finish();
+        let (handle, edge) = (Vector([2, 2]), Vector([0, 2]));
+        sim.pin(handle).unwrap();
+        sim.set_velocity(handle, Vector([1., 0.])).unwrap();
+        sim.apply_impulse(edge, Vector([0., 1.])).unwrap();
+        sim.apply_impulse_region(Vector([2, 2]), Vector([2, 2]), Vector([1., 1.]));
+
+        sim.update(1e-2);
+
+        for index in [handle, edge] {
+            assert_eq!(sim.position(index), Some(Vector::zero()), "{index:?}");
+            assert_eq!(sim.velocity(index), Some(Vector::zero()), "{index:?}");
+        }
+    }
+
+    #[test]
+    fn test_set_position_refreshes_acceleration() {
+        let mut sim = Simulation::<f64, 1>::build().origin_stiffness(1.).finish();
+        sim.set_position(Vector([0, 0]), Vector([1., 0.])).unwrap();
+
+        sim.update(1e-2);
+
+        assert!(sim.velocity(Vector([0, 0])).unwrap()[0] < 0.);
+    }
+
+    #[test]
+    fn test_particles_row_major() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+        sim.set_position(Vector([1, 2]), Vector([1., -0.5]))
+            .unwrap();
+        sim.set_velocity(Vector([2, 0]), Vector([0.25, 4.]))
+            .unwrap();
+
+        let particles: Vec<_> = sim.particles().collect();
+        assert_eq!(particles.len(), 9);
+
+        for (k, &(indices, pos, vel)) in particles.iter().enumerate() {
+            assert_eq!(index::<3, 2>(indices.map(|i| i as isize)), Some(k));
+            assert_eq!(Some(pos), sim.position(indices));
+            assert_eq!(Some(vel), sim.velocity(indices));
+        }
+    }
+
+    #[test]
+    fn test_particles_mut() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+
+        let mut count = 0;
+        for (indices, pos, vel) in sim.particles_mut() {
+            *pos = indices.map(|i| i as f64);
+            *vel = Vector([1., 2.]);
+            count += 1;
+        }
+        assert_eq!(count, 9);
+
+        assert_eq!(sim.position(Vector([2, 1])), Some(Vector([2., 1.])));
+        assert_eq!(sim.velocity(Vector([0, 2])), Some(Vector([1., 2.])));
+    }
+
+    #[test]
+    fn test_rectangular_lattice() {
+        let sim = Simulation::<f64, 1>::build().shape(Vector([4, 3])).finish();
+        assert_eq!(sim.shape(), Vector([4, 3]));
+        assert_eq!(sim.state.pos.len(), 12);
+
+        let particles: Vec<_> = sim.particles().collect();
+        assert_eq!(particles.len(), 12);
+        for (k, &(indices, _, _)) in particles.iter().enumerate() {
+            assert_eq!(deindex_in(Vector([4, 3]), k), Some(indices));
+            assert_eq!(
+                index_in(Vector([4, 3]), indices.map(|i| i as isize)),
+                Some(k)
+            );
+        }
+        assert!(sim.position(Vector([3, 2])).is_some());
+        assert_eq!(sim.position(Vector([2, 3])), None);
+
+        assert_eq!(
+            Simulation::<f64, 1>::build()
+                .shape(Vector([usize::MAX, 2]))
+                .try_finish()
+                .err(),
+            Some(BuildError::TooManyParticles)
+        );
+    }
+
+    #[test]
+    fn test_transposed_lattice_transposes_forces() {
+        for boundary in [
+            BoundaryCondition::Free,
+            BoundaryCondition::Fixed,
+            BoundaryCondition::Periodic,
+            BoundaryCondition::Reflective,
+        ] {
+            let build = |shape, stiffness| {
+                Simulation::<f64, 1>::build()
+                    .shape(shape)
+                    .boundary(boundary)
+                    .stiffness_axis(stiffness)
+                    .finish()
+            };
+            let mut wide = build(Vector([4, 3]), Vector([1., 2.]));
+            let mut tall = build(Vector([3, 4]), Vector([2., 1.]));
+            wide.set_position(Vector([3, 1]), Vector([0.5, -0.25]))
+                .unwrap();
+            tall.set_position(Vector([1, 3]), Vector([-0.25, 0.5]))
+                .unwrap();
+
+            for (indices, _, _) in wide.particles() {
+                let here = offset_in(Vector([4, 3]), indices);
+                let there = offset_in(Vector([3, 4]), Vector([indices[1], indices[0]]));
+                let (a, b) = (wide.state.acc[here], tall.state.acc[there]);
+                assert!((a[0] - b[1]).abs() < 1e-12 && (a[1] - b[0]).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+        sim.set_position(Vector([1, 1]), Vector([1., 0.])).unwrap();
+        let mut stepped = sim.clone();
+
+        sim.run(10, 0.1);
+        for _ in 0..10 {
+            stepped.update(0.1);
+        }
+
+        assert!((sim.time() - 1.).abs() < 1e-12);
+        assert_eq!(sim, stepped);
+    }
+
+    #[test]
+    fn test_update_checked_reports_blow_up() {
+        let mut sim = Simulation::<f64, 6>::build()
+            .stiffness(4.)
+            .random_init(1., 3)
+            .finish();
+        assert_eq!(sim.update_checked(1e-3), Ok(()));
+
+        let error = (0..10_000)
+            .find_map(|_| sim.update_checked(10.).err())
+            .expect("a huge dt should blow up");
+        assert!(error.index.map(|i| i < 6).all());
+        let k = offset::<6, 2>(error.index);
+        let state = sim.state();
+        assert!(!(state.pos[k].iter().chain(state.vel[k].iter())).all(f64::is_finite));
+        assert!(state.pos[..k]
+            .iter()
+            .chain(&state.vel[..k])
+            .all(|v| v.iter().all(f64::is_finite)));
+    }
+
+    #[test]
+    fn test_run_guarded_trips_on_drift() {
+        let build = || {
+            Simulation::<f64, 6>::build()
+                .stiffness(4.)
+                .random_init(1., 3)
+                .finish()
+        };
+        let mut sim = build();
+        assert_eq!(sim.run_guarded(1000, 1e-3, 0.05), Ok(()));
+        assert_eq!(sim.steps(), 1000);
+
+        let mut sim = build();
+        let initial = sim.total_energy();
+        let error = sim.run_guarded(1000, 0.5, 0.05).unwrap_err();
+        assert_eq!(sim.steps(), error.step as u64 + 1);
+        assert_eq!(error.energy, sim.total_energy());
+        assert!((error.energy - initial).abs() > 0.05 * initial);
+        // Caught long before anything overflows.
+        assert!(sim
+            .particles()
+            .all(|(_, pos, vel)| (pos + vel).norm() < 1e3));
+    }
+
+    #[test]
+    fn test_run_sampled_fires_every_stride_and_at_the_end() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+        let mut fired = Vec::new();
+        sim.run_sampled(25, 1e-2, 10, |taken, sim| fired.push((taken, sim.time)));
+
+        let taken: Vec<_> = fired.iter().map(|&(taken, _)| taken).collect();
+        assert_eq!(taken, [10, 20, 25]);
+        assert!((fired[2].1 - 0.25).abs() < 1e-12);
+
+        let mut count = 0;
+        sim.run_sampled(20, 1e-2, 10, |_, _| count += 1);
+        assert_eq!(count, 2);
+        sim.run_sampled(0, 1e-2, 10, |_, _| count += 1);
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn test_time_and_steps() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+        assert_eq!((sim.time(), sim.steps()), (0., 0));
+
+        sim.run(5, 0.2);
+        assert!((sim.time() - 1.).abs() < 1e-12);
+        assert_eq!(sim.steps(), 5);
+
+        sim.run_until(1.5, 0.2);
+        assert_eq!(sim.time(), 1.5);
+        assert_eq!(sim.steps(), 8);
+
+        sim.update_adaptive(0.1, 1.);
+        assert_eq!(sim.steps(), 9);
+
+        sim.reset();
+        assert_eq!((sim.time(), sim.steps()), (0., 0));
+    }
+
+    #[test]
+    fn test_run_with_calls_back_every_step() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+        sim.set_position(Vector([1, 1]), Vector([1., 0.])).unwrap();
+        let mut stepped = sim.clone();
+
+        let mut seen = Vec::new();
+        sim.run_with(5, 0.1, |step, sim| seen.push((step, sim.time())));
+        stepped.run(5, 0.1);
+
+        assert_eq!(
+            seen.iter().map(|&(step, _)| step).collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4]
+        );
+        assert!(seen.windows(2).all(|pair| pair[0].1 < pair[1].1));
+        assert_eq!(sim, stepped);
+    }
+
+    #[test]
+    fn test_run_until_partial_step() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+        sim.set_position(Vector([1, 1]), Vector([1., 0.])).unwrap();
+        let mut stepped = sim.clone();
+
+        sim.run_until(1.05, 0.1);
+        stepped.run(10, 0.1);
+        stepped.update(1.05 - stepped.time());
+
+        assert_eq!(sim.time(), 1.05);
+        assert_eq!(
+            sim.position(Vector([1, 1])),
+            stepped.position(Vector([1, 1]))
+        );
+
+        sim.run_until(1., 0.1);
+        assert_eq!(sim.time(), 1.05);
+    }
+
+    #[test]
+    fn test_run_until_rejects_non_positive_dt() {
+        for dt in [0., -0.1, f64::NAN] {
+            let result = std::panic::catch_unwind(|| {
+                Simulation::<f64, 3>::build().finish().run_until(1., dt);
+            });
+            assert!(result.is_err(), "dt {dt}");
+        }
+    }
+
+    #[test]
+    fn test_reset_matches_fresh_build() {
+        let build = || {
+            Simulation::<f64, 4>::build()
+                .stiffness(2.)
+                .random_init(0.25, 3)
+                .finish()
+        };
+        let mut sim = build();
+        sim.set_velocity(Vector([1, 2]), Vector([1., 0.])).unwrap();
+        sim.run(20, 1e-2);
+        assert_ne!(sim, build());
+
+        sim.reset();
+
+        assert_eq!(sim, build());
+    }
+
+    #[test]
+    fn test_set_state_restores_snapshot() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+        sim.set_position(Vector([1, 1]), Vector([1., 0.])).unwrap();
+        let snapshot = sim.state().clone();
+        let expected = sim.position(Vector([1, 1]));
+
+        sim.run(10, 0.1);
+        assert_ne!(sim.position(Vector([1, 1])), expected);
 
-        assert_eq!(index::<100, 3>(Vector([2, 10, 5])), Some(21_005));
-        assert_eq!(index::<10, 3>(Vector([2, 10, 2])), None);
-        assert_eq!(index::<10, 3>(Vector([10, 2, 2])), None);
-        assert_eq!(index::<10, 3>(Vector([9, 9, 9])), Some(999));
-        assert_eq!(index::<10, 3>(Vector([0, 0, 0])), Some(0));
-        assert_eq!(index::<10, 3>(Vector([-1, 2, 2])), None);
-        assert_eq!(index::<10, 3>(Vector([2, -1, 2])), None);
-        assert_eq!(index::<10, 3>(Vector([-1, 2, -1])), None);
+        sim.set_state(snapshot);
+        assert_eq!(sim.position(Vector([1, 1])), expected);
     }
 
+    #[cfg(feature = "serde")]
     #[test]
-    fn test_deindex() {
-        assert_eq!(deindex::<100, 2>(210), Some(Vector([2, 10])));
-        assert_eq!(deindex::<10, 2>(99), Some(Vector([9, 9])));
-        assert_eq!(deindex::<10, 2>(0), Some(Vector([0, 0])));
+    fn test_state_serde_round_trip() {
+        let mut sim = Simulation::<f64, 3>::build()
+            .stiffness(2.)
+            .damping(0.125)
+            .gravity(Vector([0., -9.81]))
+            .finish();
+        sim.set_mass(Vector([0, 2]), 3.).unwrap();
+        sim.set_position(Vector([1, 1]), Vector([1., -0.5]))
+            .unwrap();
+        sim.set_velocity(Vector([2, 0]), Vector([0.25, 4.]))
+            .unwrap();
+        sim.run(3, 0.1);
 
-        assert_eq!(deindex::<5, 3>(99), Some(Vector([3, 4, 4])));
+        let json = serde_json::to_string(sim.state()).unwrap();
+        let state: super::SimulationState<f64, 3> = serde_json::from_str(&json).unwrap();
+        assert_eq!(&state, sim.state());
 
-        assert_eq!(deindex::<100, 2>(10000), None);
-        assert_eq!(deindex::<100, 2>(-1), None);
+        // A state saved without a shape is square, and must then have
+        // `SIZE.pow(DIMS)` particles.
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value.as_object_mut().unwrap().remove("shape");
+        let unshaped = serde_json::from_value::<super::SimulationState<f64, 3>>(value.clone());
+        assert_eq!(&unshaped.unwrap(), sim.state());
+        assert!(serde_json::from_value::<super::SimulationState<f64, 4>>(value.clone()).is_err());
 
-        assert_eq!(deindex::<100, 3>(1000000), None);
-        assert_eq!(deindex::<100, 3>(-1), None);
+        value["shape"] = serde_json::json!([3, 4]);
+        assert!(serde_json::from_value::<super::SimulationState<f64, 3>>(value).is_err());
     }
 }
@@ -0,0 +1,61 @@
+//! Zero-copy [`ndarray`] views of the particle buffers, enabled by the
+//! `ndarray` feature.
+
+use ndarray::{ArrayViewD, IxDyn};
+
+use crate::{Float, Vector};
+
+use super::Simulation;
+
+/// `buffer` as an array of the grid's `shape` followed by `[DIMS]`: one
+/// axis per grid index, then one for the components. The row-major particle
+/// order is exactly the standard layout of that shape.
+fn view<T: Float, const DIMS: usize>(
+    buffer: &[Vector<T, DIMS>],
+    shape: Vector<usize, DIMS>,
+) -> ArrayViewD<'_, T> {
+    let mut shape = shape.0.to_vec();
+    shape.push(DIMS);
+    ArrayViewD::from_shape(IxDyn(&shape), bytemuck::cast_slice(buffer)).unwrap()
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    /// The positions as an array indexed by the grid indices and then the
+    /// component, e.g. `[i, j, d]` in two dimensions. Borrows the buffer
+    /// without copying.
+    pub fn positions_ndarray(&self) -> ArrayViewD<'_, T> {
+        view(&self.state.pos, self.state.shape)
+    }
+
+    /// The velocities, laid out like [`Simulation::positions_ndarray`].
+    pub fn velocities_ndarray(&self) -> ArrayViewD<'_, T> {
+        view(&self.state.vel, self.state.shape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Simulation, Vector};
+
+    #[test]
+    fn test_ndarray_views_match_buffers() {
+        let mut sim = Simulation::<f64, 3>::build().random_init(1., 2).finish();
+        sim.set_velocity(Vector([2, 0]), Vector([4., -1.])).unwrap();
+
+        let pos = sim.positions_ndarray();
+        assert_eq!(pos.shape(), [3, 3, 2]);
+        for i in 0..3 {
+            for j in 0..3 {
+                let expected = sim.position(Vector([i, j])).unwrap();
+                for d in 0..2 {
+                    assert_eq!(pos[[i, j, d]], expected[d]);
+                }
+            }
+        }
+        assert_eq!(sim.velocities_ndarray()[[2, 0, 0]], 4.);
+        assert_eq!(sim.velocities_ndarray()[[2, 0, 1]], -1.);
+
+        let sim = Simulation::<f32, 4, 3>::build().finish();
+        assert_eq!(sim.positions_ndarray().shape(), [4, 4, 4, 3]);
+    }
+}
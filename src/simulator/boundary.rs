@@ -0,0 +1,312 @@
+use crate::{
+    indexing::{deindex_in, filter_indices_in},
+    Float, Vector,
+};
+
+use super::Simulation;
+
+/// How the coupling stencil treats neighbors that fall outside the grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BoundaryCondition {
+    /// Neighbors beyond the edge are dropped, leaving edge particles with
+    /// fewer bonds.
+    #[default]
+    Free,
+    /// The grid wraps into a torus, so every particle has the full set of
+    /// neighbors.
+    Periodic,
+    /// Particles on the outer ring (any index equal to `0` or one less than
+    /// the extent of its axis)
+    /// never move: their acceleration and velocity are held at zero. They
+    /// still exert coupling forces on the interior, acting as anchors.
+    /// Neighbors beyond the edge are dropped, as with `Free`.
+    Fixed,
+    /// The edges are mirror planes through the outermost particles: a
+    /// neighbor beyond the edge is replaced by its mirror image inside, so
+    /// index `-1` stands for `1` and `SIZE` for `SIZE - 2`, with `SIZE` the
+    /// extent of the axis. An edge particle
+    /// is then bonded twice to its inner neighbor, which makes the gradient
+    /// across the edge vanish (a Neumann condition) and reflects waves
+    /// without inverting them. The mirrored bonds are not real springs and
+    /// are left out of [`Simulation::potential_energy`]. Instead an edge
+    /// particle stands for half a cell, so the energies count it, and each
+    /// bond running along the edge, at a half for every mirror plane it lies
+    /// on. With a coupling radius of one the total energy is then conserved.
+    Reflective,
+}
+
+impl BoundaryCondition {
+    /// Maps stencil indices onto a grid of the given shape, or `None` if the
+    /// neighbor does not exist under this boundary condition.
+    pub(super) fn resolve<const DIMS: usize>(
+        self,
+        shape: Vector<usize, DIMS>,
+        indices: Vector<isize, DIMS>,
+    ) -> Option<Vector<usize, DIMS>> {
+        match self {
+            BoundaryCondition::Free | BoundaryCondition::Fixed => filter_indices_in(shape, indices),
+            BoundaryCondition::Periodic => {
+                Some(indices.map2(shape, |i, extent| i.rem_euclid(extent as isize) as usize))
+            }
+            BoundaryCondition::Reflective => {
+                let mirrored = indices.map2(shape, |i, extent| {
+                    let last = extent as isize - 1;
+                    if i < 0 {
+                        -i
+                    } else if i > last {
+                        2 * last - i
+                    } else {
+                        i
+                    }
+                });
+                filter_indices_in(shape, mirrored)
+            }
+        }
+    }
+
+    /// Whether the particle at `indices` of a grid of the given shape is
+    /// held in place.
+    pub(super) fn is_clamped<const DIMS: usize>(
+        self,
+        shape: Vector<usize, DIMS>,
+        indices: Vector<usize, DIMS>,
+    ) -> bool {
+        match self {
+            BoundaryCondition::Free
+            | BoundaryCondition::Periodic
+            | BoundaryCondition::Reflective => false,
+            BoundaryCondition::Fixed => !indices
+                .map2(shape, |i, extent| 0 < i && i + 1 < extent)
+                .all(),
+        }
+    }
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    /// Zeroes the velocity of every clamped or pinned particle.
+    pub(super) fn enforce_boundary(&mut self) {
+        let state = &mut self.state;
+        for (k, vel) in state.vel.iter_mut().enumerate() {
+            let indices = deindex_in(state.shape, k).unwrap();
+            if state.pinned[k] || state.boundary.is_clamped(state.shape, indices) {
+                *vel = Vector::zero();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BoundaryCondition;
+    use crate::{indexing::offset, Connectivity, Simulation, Vector};
+
+    #[test]
+    fn test_resolve() {
+        let square = Vector([4, 4]);
+        let free = BoundaryCondition::Free;
+        assert_eq!(free.resolve(square, Vector([-1, 2])), None);
+        assert_eq!(free.resolve(square, Vector([3, 2])), Some(Vector([3, 2])));
+
+        let periodic = BoundaryCondition::Periodic;
+        assert_eq!(
+            periodic.resolve(square, Vector([-1, 2])),
+            Some(Vector([3, 2]))
+        );
+        assert_eq!(
+            periodic.resolve(square, Vector([1, 4])),
+            Some(Vector([1, 0]))
+        );
+        assert_eq!(
+            periodic.resolve(square, Vector([-1, 4])),
+            Some(Vector([3, 0]))
+        );
+
+        let reflective = BoundaryCondition::Reflective;
+        assert_eq!(
+            reflective.resolve(square, Vector([-1, 2])),
+            Some(Vector([1, 2]))
+        );
+        assert_eq!(
+            reflective.resolve(square, Vector([1, 4])),
+            Some(Vector([1, 2]))
+        );
+        assert_eq!(
+            reflective.resolve(square, Vector([-2, 5])),
+            Some(Vector([2, 1]))
+        );
+        assert_eq!(reflective.resolve(square, Vector([-4, 0])), None);
+        assert_eq!(reflective.resolve(Vector([1, 1]), Vector([-1, 0])), None);
+
+        // Every axis resolves against its own extent.
+        let shape = Vector([4, 3]);
+        assert_eq!(
+            periodic.resolve(shape, Vector([4, 3])),
+            Some(Vector([0, 0]))
+        );
+        assert_eq!(
+            reflective.resolve(shape, Vector([4, 3])),
+            Some(Vector([2, 1]))
+        );
+        assert_eq!(free.resolve(shape, Vector([3, 3])), None);
+    }
+
+    #[test]
+    fn test_reflective_wall_returns_pulse() {
+        // A Gaussian pulse moving toward index 0 at unit speed.
+        const WIDTH: f64 = 4.;
+        let mut sim = Simulation::<f64, 96, 1>::chain()
+            .stiffness(1.)
+            .origin_stiffness(0.)
+            .boundary(BoundaryCondition::Reflective)
+            .finish();
+        for (index, pos, vel) in sim.particles_mut() {
+            let x = (index[0] as f64 - 30.) / WIDTH;
+            *pos = Vector([(-x * x).exp()]);
+            *vel = Vector([-2. * x / WIDTH * (-x * x).exp()]);
+        }
+
+        // Out to the wall and back, the pulse is 30 sites from it again.
+        sim.run(3_000, 2e-2);
+        let (peak, amplitude) = sim.max_displacement();
+        assert!((28..=32).contains(&peak[0]), "peak at {peak:?}");
+        assert!(amplitude > 0.9, "amplitude {amplitude}");
+        assert!(sim.position(peak).unwrap()[0] > 0.);
+        assert!(sim.velocity(Vector([peak[0] + 2])).unwrap()[0] > 0.);
+    }
+
+    #[test]
+    fn test_periodic_is_translation_invariant() {
+        let displaced = |i: usize, j: usize| {
+            let mut sim = Simulation::<f64, 5>::build()
+                .stiffness(2.)
+                .origin_stiffness(0.5)
+                .boundary(BoundaryCondition::Periodic)
+                .finish();
+            sim.state.pos[offset::<5, 2>(Vector([i, j]))] = Vector([1., -0.5]);
+            sim.refresh_acc();
+            sim
+        };
+
+        let corner = displaced(0, 0);
+        let center = displaced(2, 2);
+
+        for i in 0..5 {
+            for j in 0..5 {
+                assert_eq!(
+                    corner.state.acc[offset::<5, 2>(Vector([i, j]))],
+                    center.state.acc[offset::<5, 2>(Vector([(i + 2) % 5, (j + 2) % 5]))]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_periodic_uniform_field_stays_uniform() {
+        let mut sim = Simulation::<f64, 4>::build()
+            .stiffness(2.)
+            .origin_stiffness(0.5)
+            .boundary(BoundaryCondition::Periodic)
+            .finish();
+        sim.state.pos.fill(Vector([1., -0.5]));
+        sim.refresh_acc();
+
+        for _ in 0..100 {
+            sim.update(1e-2);
+        }
+
+        let expected = sim.state.pos[0];
+        assert_ne!(expected, Vector([1., -0.5]));
+        for &pos in sim.state.pos.iter() {
+            assert_eq!(pos, expected);
+        }
+    }
+
+    #[test]
+    fn test_free_uniform_translation_exerts_no_force() {
+        let builders = [
+            Simulation::<f64, 5>::build(),
+            Simulation::build().connectivity(Connectivity::WithDiagonals),
+            Simulation::build().coupling_radius(2, |r| 1. / r),
+        ];
+        for builder in builders {
+            let mut sim = builder
+                .stiffness(2.)
+                .cubic_coupling(0.5)
+                .origin_stiffness(0.)
+                .boundary(BoundaryCondition::Free)
+                .finish();
+            let shift = Vector([0.3, -1.7]);
+            sim.state.pos.fill(shift);
+            sim.refresh_acc();
+
+            // At corners, edges and in the interior alike, every bond that is
+            // present has zero extension and a missing one contributes nothing.
+            for (name, index) in [
+                ("corner", [0, 0]),
+                ("corner", [4, 4]),
+                ("edge", [0, 2]),
+                ("edge", [3, 4]),
+                ("interior", [2, 2]),
+                ("interior", [1, 3]),
+            ] {
+                let acc = sim.state.acc[offset::<5, 2>(Vector(index))];
+                assert_eq!(acc, Vector::zero(), "{name} {index:?}");
+            }
+            assert!(sim.state.acc.iter().all(|&acc| acc == Vector::zero()));
+
+            sim.run(100, 1e-2);
+            assert!(sim.state.pos.iter().all(|&pos| pos == shift));
+        }
+    }
+
+    #[test]
+    fn test_is_clamped() {
+        let square = Vector([4, 4]);
+        let fixed = BoundaryCondition::Fixed;
+        assert!(fixed.is_clamped(square, Vector([0, 2])));
+        assert!(fixed.is_clamped(square, Vector([2, 3])));
+        assert!(fixed.is_clamped(square, Vector([3, 3])));
+        assert!(!fixed.is_clamped(square, Vector([1, 2])));
+
+        assert!(!BoundaryCondition::Free.is_clamped(square, Vector([0, 0])));
+        assert!(!BoundaryCondition::Periodic.is_clamped(square, Vector([0, 0])));
+
+        // The outer ring of a 4x3 grid lies at 3 along the first axis and 2
+        // along the second.
+        let shape = Vector([4, 3]);
+        assert!(fixed.is_clamped(shape, Vector([1, 2])));
+        assert!(fixed.is_clamped(shape, Vector([3, 1])));
+        assert!(!fixed.is_clamped(shape, Vector([2, 1])));
+    }
+
+    #[test]
+    fn test_fixed_boundary_stays_put() {
+        let mut sim = Simulation::<f64, 5>::build()
+            .stiffness(2.)
+            .origin_stiffness(0.5)
+            .gravity(Vector([0., -1.]))
+            .boundary(BoundaryCondition::Fixed)
+            .finish();
+        sim.state.pos[offset::<5, 2>(Vector([2, 2]))] = Vector([1., -0.5]);
+        sim.state.pos[offset::<5, 2>(Vector([1, 3]))] = Vector([-0.5, 0.25]);
+        sim.state.pos[offset::<5, 2>(Vector([0, 2]))] = Vector([0.25, 0.]);
+        sim.refresh_acc();
+
+        let initial = sim.state.pos.clone();
+        for _ in 0..1_000 {
+            sim.update(1e-2);
+        }
+
+        for i in 0..5 {
+            for j in 0..5 {
+                let k = offset::<5, 2>(Vector([i, j]));
+                if [i, j].contains(&0) || [i, j].contains(&4) {
+                    assert_eq!(sim.state.pos[k], initial[k]);
+                } else {
+                    assert_ne!(sim.state.pos[k], initial[k]);
+                }
+            }
+        }
+    }
+}
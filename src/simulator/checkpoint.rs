@@ -0,0 +1,393 @@
+//! A compact binary checkpoint format.
+//!
+//! A checkpoint is a small header followed by the raw bytes of the simulated
+//! time and step count, the integrator settings, the initial positions, the physical
+//! parameters and the particle buffers, all in native byte order. The header
+//! records `SIZE`, `DIMS`, the float type's [`Float::TAG`] and a byte-order
+//! marker, and loading refuses checkpoints whose header does not match. The
+//! extents of the grid follow and are restored with the rest, and loading
+//! refuses a shape with more particles than the rest of the file holds, or
+//! a coupling radius reaching past the whole grid. User force fields are not
+//! saved.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use bytemuck::Pod;
+
+use crate::{indexing::shape_len, BoundaryCondition, Connectivity, Float, Integrator, Vector};
+
+use super::Simulation;
+
+const MAGIC: [u8; 8] = *b"RTDRIVER";
+const VERSION: u32 = 12;
+const BYTE_ORDER: u32 = 0x0102_0304;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_pod<P: Pod>(w: &mut impl Write, value: &P) -> io::Result<()> {
+    w.write_all(bytemuck::bytes_of(value))
+}
+
+fn read_pod<P: Pod>(r: &mut impl Read) -> io::Result<P> {
+    let mut value = P::zeroed();
+    r.read_exact(bytemuck::bytes_of_mut(&mut value))?;
+    Ok(value)
+}
+
+fn write_slice<P: Pod>(w: &mut impl Write, values: &[P]) -> io::Result<()> {
+    w.write_all(bytemuck::cast_slice(values))
+}
+
+fn read_slice<P: Pod>(r: &mut impl Read, values: &mut [P]) -> io::Result<()> {
+    r.read_exact(bytemuck::cast_slice_mut(values))
+}
+
+const INTEGRATORS: [Integrator; 5] = [
+    Integrator::Euler,
+    Integrator::SymplecticEuler,
+    Integrator::VelocityVerlet,
+    Integrator::Leapfrog,
+    Integrator::RungeKutta4,
+];
+
+const BOUNDARIES: [BoundaryCondition; 4] = [
+    BoundaryCondition::Free,
+    BoundaryCondition::Periodic,
+    BoundaryCondition::Fixed,
+    BoundaryCondition::Reflective,
+];
+
+const CONNECTIVITIES: [Connectivity; 3] = [
+    Connectivity::AxisNeighbors,
+    Connectivity::WithDiagonals,
+    Connectivity::Moore,
+];
+
+fn tag<E: PartialEq>(variants: &[E], value: &E) -> u8 {
+    variants.iter().position(|v| v == value).unwrap() as u8
+}
+
+fn untag<E: Copy>(variants: &[E], tag: u8) -> io::Result<E> {
+    variants
+        .get(tag as usize)
+        .copied()
+        .ok_or_else(|| invalid_data("unknown enum tag in checkpoint"))
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    pub fn save_checkpoint(&self, path: &Path) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_checkpoint(&mut w)?;
+        w.flush()
+    }
+
+    pub fn load_checkpoint(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Self::read_checkpoint(&mut BufReader::new(file), len)
+    }
+
+    fn write_checkpoint(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&MAGIC)?;
+        write_pod(w, &VERSION)?;
+        write_pod(w, &BYTE_ORDER)?;
+        write_pod(w, &(SIZE as u64))?;
+        write_pod(w, &(DIMS as u64))?;
+        write_pod(w, &T::TAG)?;
+        write_slice(w, &self.state.shape.map(|extent| extent as u64).0)?;
+
+        write_pod(w, &self.time)?;
+        write_pod(w, &self.steps)?;
+        write_pod(w, &tag(&INTEGRATORS, &self.integrator))?;
+        write_pod(w, &self.min_dt)?;
+        write_slice(w, &self.initial_pos)?;
+
+        let state = &self.state;
+        write_pod(w, &state.stiffness)?;
+        write_pod(w, &state.rest_length)?;
+        write_pod(w, &state.cubic_coupling)?;
+        write_pod(w, &tag(&CONNECTIVITIES, &state.connectivity))?;
+        write_pod(w, &state.diagonal_stiffness)?;
+        write_pod(w, &(state.coupling_radius as u64))?;
+        write_slice(w, &state.long_range_stiffness)?;
+        write_pod(w, &(state.bond_stiffness.is_some() as u8))?;
+        if let Some(field) = &state.bond_stiffness {
+            write_slice(w, field)?;
+        }
+        write_pod(w, &state.origin_stiffness)?;
+        write_pod(w, &state.damping)?;
+        write_pod(w, &state.gravity)?;
+        write_pod(w, &tag(&BOUNDARIES, &state.boundary))?;
+        write_slice(w, &state.mass)?;
+        write_slice(w, &state.pos)?;
+        write_slice(w, &state.vel)?;
+        write_slice(w, &state.acc)?;
+        let pinned: Box<[u8]> = state.pinned.iter().map(|&p| p as u8).collect();
+        write_slice(w, &pinned)
+    }
+
+    /// Reads a checkpoint of `len` bytes.
+    fn read_checkpoint(r: &mut impl Read, len: u64) -> io::Result<Self> {
+        if read_pod::<[u8; 8]>(r)? != MAGIC {
+            return Err(invalid_data("not a checkpoint"));
+        }
+        if read_pod::<u32>(r)? != VERSION {
+            return Err(invalid_data("unsupported checkpoint version"));
+        }
+        if read_pod::<u32>(r)? != BYTE_ORDER {
+            return Err(invalid_data("checkpoint byte order mismatch"));
+        }
+        if read_pod::<u64>(r)? != SIZE as u64 {
+            return Err(invalid_data("checkpoint SIZE mismatch"));
+        }
+        if read_pod::<u64>(r)? != DIMS as u64 {
+            return Err(invalid_data("checkpoint DIMS mismatch"));
+        }
+        if read_pod::<u8>(r)? != T::TAG {
+            return Err(invalid_data("checkpoint float type mismatch"));
+        }
+        let mut shape = [0u64; DIMS];
+        read_slice(r, &mut shape)?;
+        let shape = Vector(shape).map(|extent| usize::try_from(extent).unwrap_or(usize::MAX));
+
+        // Every particle has at least its initial position, mass, position,
+        // velocity, acceleration and pinned flag in the rest of the file, so
+        // a shape that would not fit is corrupt and is refused before its
+        // buffers are allocated.
+        let header = MAGIC.len() + 2 * size_of::<u32>() + 2 * size_of::<u64>() + 1;
+        let remaining = len.saturating_sub((header + DIMS * size_of::<u64>()) as u64);
+        let record = (4 * DIMS + 1) * size_of::<T>() + 1;
+        let fits = shape_len(shape)
+            .and_then(|particles| particles.checked_mul(record))
+            .is_some_and(|bytes| bytes as u64 <= remaining);
+        if !fits {
+            return Err(invalid_data("checkpoint shape does not match its length"));
+        }
+
+        let mut sim = Self::build()
+            .shape(shape)
+            .try_finish()
+            .map_err(|_| invalid_data("checkpoint shape is too large"))?;
+        sim.time = read_pod(r)?;
+        sim.steps = read_pod(r)?;
+        sim.integrator = untag(&INTEGRATORS, read_pod(r)?)?;
+        sim.min_dt = read_pod(r)?;
+        read_slice(r, &mut sim.initial_pos)?;
+
+        let state = &mut sim.state;
+        state.stiffness = read_pod(r)?;
+        state.rest_length = read_pod(r)?;
+        state.cubic_coupling = read_pod(r)?;
+        state.connectivity = untag(&CONNECTIVITIES, read_pod(r)?)?;
+        state.diagonal_stiffness = read_pod(r)?;
+        // The stencil of a radius has about `(2 * radius + 1).pow(DIMS)`
+        // offsets, so a corrupt one is refused before it is enumerated. No
+        // radius beyond the largest extent bonds any further particles, and
+        // an empty grid bonds none at all.
+        let largest = shape.iter().max().unwrap_or(0).min(state.mass.len());
+        state.coupling_radius = match read_pod::<u64>(r)? {
+            radius if radius <= largest.max(1) as u64 => radius as usize,
+            _ => return Err(invalid_data("checkpoint coupling radius is too large")),
+        };
+        let long_range = state
+            .connectivity
+            .long_range_stencil::<DIMS>(state.coupling_radius)
+            .len();
+        state.long_range_stiffness = vec![T::zero(); long_range].into_boxed_slice();
+        read_slice(r, &mut state.long_range_stiffness)?;
+        state.bond_stiffness = match read_pod::<u8>(r)? {
+            0 => None,
+            1 => {
+                let mut field = vec![T::zero(); state.mass.len()].into_boxed_slice();
+                read_slice(r, &mut field)?;
+                Some(field)
+            }
+            _ => return Err(invalid_data("invalid bond stiffness flag in checkpoint")),
+        };
+        state.origin_stiffness = read_pod(r)?;
+        state.damping = read_pod(r)?;
+        state.gravity = read_pod(r)?;
+        state.boundary = untag(&BOUNDARIES, read_pod(r)?)?;
+        read_slice(r, &mut state.mass)?;
+        read_slice(r, &mut state.pos)?;
+        read_slice(r, &mut state.vel)?;
+        read_slice(r, &mut state.acc)?;
+        let mut pinned = vec![0u8; state.pinned.len()];
+        read_slice(r, &mut pinned)?;
+        for (p, byte) in state.pinned.iter_mut().zip(pinned) {
+            *p = match byte {
+                0 => false,
+                1 => true,
+                _ => return Err(invalid_data("invalid pinned flag in checkpoint")),
+            };
+        }
+        sim.sync_neighbors();
+
+        Ok(sim)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, path::PathBuf};
+
+    use crate::{BoundaryCondition, Connectivity, Integrator, Simulation, Vector};
+
+    fn checkpoint_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rtdriver-{}-{name}.ckpt", std::process::id()))
+    }
+
+    fn evolved() -> Simulation<f64, 4> {
+        let mut sim = Simulation::build()
+            .stiffness(2.)
+            .damping(0.125)
+            .random_init(0.5, 1)
+            .connectivity(Connectivity::WithDiagonals)
+            .coupling_radius(2, |distance| 1. / distance)
+            .gravity(Vector([0., -9.81]))
+            .boundary(BoundaryCondition::Periodic)
+            .integrator(Integrator::Leapfrog)
+            .finish();
+        sim.set_mass(Vector([0, 2]), 3.).unwrap();
+        sim.set_position(Vector([1, 1]), Vector([1., -0.5]))
+            .unwrap();
+        sim.set_velocity(Vector([2, 0]), Vector([0.25, 4.]))
+            .unwrap();
+        sim.pin(Vector([3, 3])).unwrap();
+        sim.set_bond_stiffness_field((0..16).map(|k| 1. + k as f64 / 8.).collect())
+            .unwrap();
+        sim.run(5, 0.1);
+        sim
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let sim = evolved();
+        let path = checkpoint_path("round-trip");
+
+        sim.save_checkpoint(&path).unwrap();
+        let mut loaded = Simulation::<f64, 4>::load_checkpoint(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.state(), sim.state());
+        assert_eq!(loaded.time(), sim.time());
+        assert_eq!(loaded.steps(), sim.steps());
+        assert_eq!(loaded.integrator, sim.integrator);
+        assert_eq!(loaded.initial_pos, sim.initial_pos);
+
+        let mut sim = sim;
+        sim.run(5, 0.1);
+        loaded.run(5, 0.1);
+        assert_eq!(loaded.state(), sim.state());
+    }
+
+    #[test]
+    fn test_checkpoint_keeps_shape() {
+        let mut sim = Simulation::<f64, 4>::build()
+            .shape(Vector([5, 2]))
+            .random_init(0.5, 3)
+            .finish();
+        sim.run(5, 0.1);
+        let path = checkpoint_path("shape");
+
+        sim.save_checkpoint(&path).unwrap();
+        let loaded = Simulation::<f64, 4>::load_checkpoint(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.shape(), Vector([5, 2]));
+        assert_eq!(loaded.state(), sim.state());
+    }
+
+    #[test]
+    fn test_checkpoint_rejects_corrupt_shape() {
+        let path = checkpoint_path("corrupt-shape");
+        evolved().save_checkpoint(&path).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+
+        // The shape follows the 33 header bytes.
+        let shape: [u64; 2] = [1 << 32, 1 << 31];
+        bytes[33..49].copy_from_slice(bytemuck::cast_slice(&shape));
+        std::fs::write(&path, &bytes).unwrap();
+        let huge = Simulation::<f64, 4>::load_checkpoint(&path).unwrap_err();
+
+        let shape: [u64; 2] = [4, 5];
+        bytes[33..49].copy_from_slice(bytemuck::cast_slice(&shape));
+        std::fs::write(&path, &bytes).unwrap();
+        let longer = Simulation::<f64, 4>::load_checkpoint(&path).unwrap_err();
+
+        let truncated = bytes[..bytes.len() / 2].to_vec();
+        std::fs::write(&path, truncated).unwrap();
+        let truncated = Simulation::<f64, 4>::load_checkpoint(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        for error in [huge, longer, truncated] {
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(
+                error.to_string(),
+                "checkpoint shape does not match its length"
+            );
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_rejects_corrupt_radius() {
+        let path = checkpoint_path("corrupt-radius");
+        let mut sim = evolved();
+        sim.state.coupling_radius = 1 << 40;
+        sim.save_checkpoint(&path).unwrap();
+        let error = Simulation::<f64, 4>::load_checkpoint(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "checkpoint coupling radius is too large");
+
+        // The default radius of an empty grid still loads.
+        let empty = Simulation::<f64, 4>::build().shape(Vector([0, 4])).finish();
+        empty.save_checkpoint(&path).unwrap();
+        let loaded = Simulation::<f64, 4>::load_checkpoint(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.state(), empty.state());
+    }
+
+    #[test]
+    fn test_checkpoint_rejects_mismatch() {
+        let path = checkpoint_path("mismatch");
+        evolved().save_checkpoint(&path).unwrap();
+
+        let wrong_size = Simulation::<f64, 3>::load_checkpoint(&path).unwrap_err();
+        let wrong_float = Simulation::<f32, 4>::load_checkpoint(&path).unwrap_err();
+        let wrong_dims = Simulation::<f64, 4, 3>::load_checkpoint(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(wrong_size.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(wrong_float.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(wrong_dims.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    #[cfg(feature = "half")]
+    fn test_checkpoint_tells_f16_from_bf16() {
+        use half::{bf16, f16};
+
+        let path = checkpoint_path("half");
+        Simulation::<f16, 4>::build()
+            .random_init(f16::ONE, 2)
+            .finish()
+            .save_checkpoint(&path)
+            .unwrap();
+
+        let same = Simulation::<f16, 4>::load_checkpoint(&path);
+        let other = Simulation::<bf16, 4>::load_checkpoint(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(same.is_ok());
+        assert_eq!(other.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(other.to_string(), "checkpoint float type mismatch");
+    }
+}
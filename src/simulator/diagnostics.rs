@@ -0,0 +1,798 @@
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{
+    indexing::{deindex_in, offset_in, shape_strides},
+    vector::Compensated,
+    BoundaryCondition, Float, Vector,
+};
+
+use super::{Simulation, SimulationState};
+
+/// Particles per block of [`block_sum`]. Fixed, so that how a sum is
+/// associated does not depend on the number of threads.
+const SUM_BLOCK: usize = 1024;
+
+/// `Σ term(k)` for `k` in `0..len`, summing each block of [`SUM_BLOCK`]
+/// terms in order, in parallel with the `rayon` feature, and then the
+/// block sums in order, all with compensated summation. The result is the
+/// same on any number of threads, but can differ from summing the terms one
+/// after another by rounding.
+fn block_sum<T: Float>(len: usize, term: impl Fn(usize) -> T + Sync) -> T {
+    let block = |b: usize| {
+        let mut sum = Compensated::new();
+        for k in b * SUM_BLOCK..len.min((b + 1) * SUM_BLOCK) {
+            sum.add(term(k));
+        }
+        sum.total()
+    };
+    let blocks = len.div_ceil(SUM_BLOCK);
+    #[cfg(feature = "rayon")]
+    let sums: Vec<T> = (0..blocks).into_par_iter().map(block).collect();
+    #[cfg(not(feature = "rayon"))]
+    let sums: Vec<T> = (0..blocks).map(block).collect();
+
+    let mut sum = Compensated::new();
+    for block_sum in sums {
+        sum.add(block_sum);
+    }
+    sum.total()
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    /// `Σ m |vel|² / 2` over all particles. Like the other energies, it is
+    /// accumulated with compensated summation, so large `f32` lattices do not
+    /// lose precision to the number of terms. Edge particles of
+    /// [`Reflective`](crate::BoundaryCondition::Reflective) boundaries count
+    /// in part.
+    pub fn kinetic_energy(&self) -> T {
+        let SimulationState { mass, vel, .. } = &self.state;
+        let half = T::one() / (T::one() + T::one());
+
+        let mut energy = Compensated::new();
+        for (k, (&m, &v)) in mass.iter().zip(vel.iter()).enumerate() {
+            energy.add(self.mirror_weight(k, k) * half * m * v.norm_squared());
+        }
+        energy.total()
+    }
+
+    /// Energy stored in the origin springs, the coupling springs and the
+    /// gravitational field. Each coupling bond is counted once, through its
+    /// neighbor in the positive direction of the stencil. The mirrored bonds
+    /// of [`Reflective`](crate::BoundaryCondition::Reflective) boundaries are
+    /// not counted, and the edge particles are weighted as in
+    /// [`Simulation::kinetic_energy`].
+    pub fn potential_energy(&self) -> T {
+        let SimulationState {
+            origin_stiffness,
+            mass,
+            gravity,
+            pos,
+            ..
+        } = &self.state;
+        let half = T::one() / (T::one() + T::one());
+
+        let mut energy = Compensated::new();
+        for (k, &position_here) in pos.iter().enumerate() {
+            let weight = self.mirror_weight(k, k);
+            energy.add(weight * half * *origin_stiffness * position_here.norm_squared());
+            energy.add(-(weight * mass[k] * gravity.dot(position_here)));
+        }
+        for (k, n, kind, up) in self.coupling_bonds() {
+            energy.add(self.bond_energy(k, n, kind, up));
+        }
+        energy.total()
+    }
+
+    /// [`Simulation::kinetic_energy`], summed in fixed-size blocks in
+    /// parallel with the `rayon` feature. Bit-identical on any number of
+    /// threads, and equal to the serial sum up to rounding.
+    pub fn par_kinetic_energy(&self) -> T {
+        let SimulationState { mass, vel, .. } = &self.state;
+        let half = T::one() / (T::one() + T::one());
+        block_sum(vel.len(), |k| {
+            self.mirror_weight(k, k) * half * mass[k] * vel[k].norm_squared()
+        })
+    }
+
+    /// [`Simulation::potential_energy`], summed like
+    /// [`Simulation::par_kinetic_energy`]: every particle contributes its
+    /// origin spring, its gravitational energy and the bonds counted
+    /// through it.
+    pub fn par_potential_energy(&self) -> T {
+        let SimulationState {
+            origin_stiffness,
+            mass,
+            gravity,
+            pos,
+            ..
+        } = &self.state;
+        let half = T::one() / (T::one() + T::one());
+        let bonds = self.neighbors.unique_bonds();
+        let mut energy = block_sum(pos.len(), |k| {
+            let energy =
+                half * *origin_stiffness * pos[k].norm_squared() - mass[k] * gravity.dot(pos[k]);
+            self.mirror_weight(k, k) * energy
+        });
+        energy += block_sum(bonds.len(), |b| {
+            let (k, n, kind, step) = bonds[b];
+            self.bond_energy(k, n, kind, self.neighbors.step(step))
+        });
+        energy
+    }
+
+    /// The energy of the coupling bond of `kind` from `k` to `n`, `up`
+    /// being the lattice offset between them.
+    fn bond_energy(&self, k: usize, n: usize, kind: usize, up: Vector<isize, DIMS>) -> T {
+        let state = &self.state;
+        let SimulationState {
+            rest_length,
+            cubic_coupling,
+            bond_stiffness,
+            pos,
+            ..
+        } = state;
+        let half = T::one() / (T::one() + T::one());
+        let quarter = half * half;
+
+        let extension = pos[n] - pos[k];
+        let stretch = if rest_length.is_zero() {
+            extension.norm()
+        } else {
+            // Measured on the separation, like `SimulationState::bond_force`.
+            let lattice = up.map(|i| T::from(i).unwrap());
+            (lattice + extension).norm() - *rest_length * lattice.norm()
+        };
+        let mut energy = half * state.spring_constant(kind) * stretch * stretch;
+        for d in extension {
+            energy += quarter * *cubic_coupling * d * d * d * d;
+        }
+        if let Some(field) = bond_stiffness {
+            energy = energy * half * (field[k] + field[n]);
+        }
+        self.mirror_weight(k, n) * energy
+    }
+
+    /// The share of the particle `k`, or of the bond from `k` to `n`, that
+    /// counts towards the energies: a half for every mirror plane of a
+    /// [`Reflective`](crate::BoundaryCondition::Reflective) boundary that both
+    /// ends lie on, and one otherwise. An edge particle feels its inner
+    /// bonds twice, which is what a particle of half the mass would feel, so
+    /// only with these weights are the energies conserved.
+    fn mirror_weight(&self, k: usize, n: usize) -> T {
+        let mut weight = T::one();
+        if self.state.boundary != BoundaryCondition::Reflective {
+            return weight;
+        }
+        let half = T::one() / (T::one() + T::one());
+        let shape = self.state.shape;
+        let (here, there) = (deindex_in(shape, k).unwrap(), deindex_in(shape, n).unwrap());
+        for axis in 0..DIMS {
+            let i = here[axis];
+            if i == there[axis] && (i == 0 || i == shape[axis] - 1) {
+                weight *= half;
+            }
+        }
+        weight
+    }
+
+    /// Every coupling bond once, as the flat offsets `(k, n)` of its ends,
+    /// its kind as tagged by `Connectivity::bond_stencil` and the lattice
+    /// offset from `k` to `n`, `n` being `k`'s neighbor in the positive
+    /// direction of the stencil. The mirrored bonds of
+    /// [`Reflective`](crate::BoundaryCondition::Reflective) boundaries are
+    /// left out. Read off the precomputed neighbor lists.
+    fn coupling_bonds(
+        &self,
+    ) -> impl Iterator<Item = (usize, usize, usize, Vector<isize, DIMS>)> + '_ {
+        (self.neighbors.unique_bonds().iter())
+            .map(|&(k, n, kind, step)| (k, n, kind, self.neighbors.step(step)))
+    }
+
+    /// The virial stress `[a][b]`, the sum over every coupling bond of the
+    /// force it exerts on one end along `a` times the separation of the
+    /// other end along `b`, divided by the volume of the lattice, whose
+    /// spacing is one, so one per particle. The separation is the lattice offset
+    /// plus the difference of the ends' positions. Tension is positive and
+    /// compression negative. Bonds are enumerated as by
+    /// [`Simulation::potential_energy`]; the origin springs, gravity and
+    /// the kinetic contribution are not included.
+    pub fn virial_stress(&self) -> [[T; DIMS]; DIMS] {
+        let state = &self.state;
+        let half = T::one() / (T::one() + T::one());
+        let mut stress = [[T::zero(); DIMS]; DIMS];
+        for (k, n, kind, up) in self.coupling_bonds() {
+            let extension = state.pos[n] - state.pos[k];
+            let mut force = state.bond_force(extension, up, kind);
+            if let Some(field) = &state.bond_stiffness {
+                force *= half * (field[k] + field[n]);
+            }
+            let separation = up.map(|i| T::from(i).unwrap()) + extension;
+            for (row, f) in stress.iter_mut().zip(force) {
+                for (entry, r) in row.iter_mut().zip(separation) {
+                    *entry += f * r;
+                }
+            }
+        }
+        let volume = T::from(state.pos.len()).unwrap();
+        stress.map(|row| row.map(|entry| entry / volume))
+    }
+
+    pub fn total_energy(&self) -> T {
+        self.kinetic_energy() + self.potential_energy()
+    }
+
+    /// `Σ m vel` over all particles.
+    pub fn total_momentum(&self) -> Vector<T, DIMS> {
+        let SimulationState { mass, vel, .. } = &self.state;
+
+        let mut momentum = Vector::zero();
+        for (&m, &v) in mass.iter().zip(vel.iter()) {
+            momentum += v * m;
+        }
+        momentum
+    }
+
+    /// Mass-weighted mean position of all particles.
+    pub fn center_of_mass(&self) -> Vector<T, DIMS> {
+        let SimulationState { mass, pos, .. } = &self.state;
+
+        let mut total_mass = T::zero();
+        let mut moment: Vector<T, DIMS> = Vector::zero();
+        for (&m, &x) in mass.iter().zip(pos.iter()) {
+            total_mass += m;
+            moment += x * m;
+        }
+        moment / total_mass
+    }
+
+    /// The largest absolute difference between corresponding position
+    /// components of `self` and `other`, or NaN if any of them is NaN.
+    pub fn max_abs_diff(&self, other: &Self) -> T {
+        let mut diff = T::zero();
+        for (&a, &b) in self.state.pos.iter().zip(other.state.pos.iter()) {
+            for d in (a - b).iter() {
+                if d.is_nan() {
+                    return d;
+                }
+                diff = diff.max(d.abs());
+            }
+        }
+        diff
+    }
+
+    /// Measures how fast a disturbance crosses the lattice, in lattice
+    /// spacings per unit time.
+    ///
+    /// Kicks the particle at the center of the grid along the first axis and
+    /// steps with `dt` until the particle a quarter of the grid's extent
+    /// away along the last axis has moved by more than a twentieth of the largest
+    /// displacement of the kicked one, then returns that distance over the
+    /// time taken. The dynamics are linear, so the kick is simply added to
+    /// whatever the lattice is doing; the simulation is left where the
+    /// measurement ended. Returns zero if the lattice is too small to
+    /// measure or the disturbance does not arrive within ten times the
+    /// travel time at `sqrt(stiffness / mass)`, the speed along the stiffest
+    /// axis for the lightest mass.
+    pub fn measure_wave_speed(&mut self, dt: T) -> T {
+        let shape = self.state.shape;
+        let radius = shape[DIMS - 1] / 4;
+        if radius == 0 {
+            return T::zero();
+        }
+        let center = shape / Vector::broadcast(2);
+        let probe = center.update(|axis, i| if axis == DIMS - 1 { i + radius } else { i });
+        let (center, probe) = (offset_in(shape, center), offset_in(shape, probe));
+        let distance = T::from(radius).unwrap();
+
+        let state = &self.state;
+        let mut stiffness = state.stiffness.iter().fold(T::zero(), T::max);
+        if let Some(field) = &state.bond_stiffness {
+            stiffness *= field.iter().copied().fold(T::zero(), T::max);
+        }
+        let mass = state.mass.iter().copied().fold(T::infinity(), T::min);
+        let speed = (stiffness / mass).sqrt();
+        let deadline = self.time + T::from(10).unwrap() * distance / speed;
+        if !deadline.is_finite() {
+            return T::zero();
+        }
+
+        let start = (self.state.pos[center], self.state.pos[probe]);
+        let (started, mut peak) = (self.time, T::zero());
+        self.state.vel[center][0] += T::one();
+        self.acc_stale = true;
+        while self.time < deadline {
+            self.update(dt);
+            peak = peak.max((self.state.pos[center] - start.0).norm());
+            let twentieth = T::from(0.05).unwrap();
+            if (self.state.pos[probe] - start.1).norm() > twentieth * peak {
+                return distance / (self.time - started);
+            }
+        }
+        T::zero()
+    }
+
+    /// `|pos|` of every particle, its distance from its lattice site, in
+    /// row-major order.
+    pub fn displacement_field(&self) -> Box<[T]> {
+        self.state.pos.iter().map(|pos| pos.norm()).collect()
+    }
+
+    /// Grid index and `|pos|` of the particle farthest from its lattice
+    /// site, the first in row-major order on a tie.
+    pub fn max_displacement(&self) -> (Vector<usize, DIMS>, T) {
+        let (k, max) = self
+            .state
+            .pos
+            .iter()
+            .map(|pos| pos.norm())
+            .enumerate()
+            .fold((0, T::zero()), |(k, max), (n, displacement)| {
+                if displacement > max {
+                    (n, displacement)
+                } else {
+                    (k, max)
+                }
+            });
+        (deindex_in(self.state.shape, k).unwrap(), max)
+    }
+
+    /// The position at `p`, given in grid index coordinates, interpolated
+    /// multilinearly between the `2.pow(DIMS)` surrounding particles:
+    /// bilinearly between four in two dimensions. Coordinates outside the
+    /// grid are clamped to its edge, whatever the boundary condition.
+    pub fn sample_displacement(&self, p: Vector<T, DIMS>) -> Vector<T, DIMS> {
+        let shape = self.state.shape;
+        let p = p.map2(shape, |x, extent| {
+            x.max(T::zero())
+                .min(T::from(extent.saturating_sub(1)).unwrap())
+        });
+        // The lower corner of the cell, moved back one on the last grid
+        // point so the upper corner exists.
+        let lo = p.map2(shape, |x, extent| {
+            x.to_usize().unwrap().min(extent.saturating_sub(2))
+        });
+        let frac = p - lo.map(|i| T::from(i).unwrap());
+
+        let mut sample = Vector::zero();
+        for corner in 0..1usize << DIMS {
+            let upper = Vector::<bool, DIMS>::from_idx(|axis| corner >> axis & 1 == 1);
+            let weight = upper
+                .map2(frac, |upper, f| if upper { f } else { T::one() - f })
+                .product();
+            if weight.is_zero() {
+                continue;
+            }
+            let index = lo.map2(upper, |i, upper| i + upper as usize);
+            sample += self.state.pos[offset_in(shape, index)] * weight;
+        }
+        sample
+    }
+
+    /// The displacement gradient `[a][b] = ∂pos[a] / ∂x[b]` at every
+    /// particle, in row-major order, with `x[b]` the grid index along axis
+    /// `b`. Its symmetric part is the linear strain. Derivatives are central
+    /// differences of the neighboring positions, one-sided at the edges of
+    /// the grid whatever the boundary condition, and zero along an axis with
+    /// a single particle.
+    pub fn strain_field(&self) -> Box<[[[T; DIMS]; DIMS]]> {
+        let pos = &self.state.pos;
+        let shape = self.state.shape;
+        let strides = shape_strides(shape);
+        (0..pos.len())
+            .map(|k| {
+                let here = deindex_in(shape, k).unwrap();
+                let mut gradient = [[T::zero(); DIMS]; DIMS];
+                for b in 0..DIMS {
+                    let (down, up) = (here[b].saturating_sub(1), (here[b] + 1).min(shape[b] - 1));
+                    if up == down {
+                        continue;
+                    }
+                    let up_k = k - here[b] * strides[b] + up * strides[b];
+                    let down_k = k - here[b] * strides[b] + down * strides[b];
+                    let slope = (pos[up_k] - pos[down_k]) / T::from(up - down).unwrap();
+                    for (row, d) in gradient.iter_mut().zip(slope) {
+                        row[b] = d;
+                    }
+                }
+                gradient
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{indexing::offset, BoundaryCondition, Connectivity, Simulation, Vector};
+
+    #[test]
+    fn test_compensated_energy() {
+        let mut sim = Simulation::<f32, 512>::build().finish();
+        for (_, _, vel) in sim.particles_mut() {
+            *vel = Vector([0.1, 0.2]);
+        }
+        // What the energy of every particle rounds to, summed exactly.
+        let term = 0.5f32 * (0.1f32 * 0.1 + 0.2 * 0.2);
+        let exact = term as f64 * (512 * 512) as f64;
+        let naive = sim.particles().fold(0f32, |sum, _| sum + term);
+
+        let error = |energy: f32| (energy as f64 - exact).abs() / exact;
+        assert!(error(naive) > 1e-4, "naive error {}", error(naive));
+        assert!(error(sim.kinetic_energy()) < 1e-7);
+        assert!(error(sim.par_kinetic_energy()) < 1e-7);
+    }
+
+    #[test]
+    fn test_parallel_energy_matches_serial() {
+        // Eighths and their squares add up exactly in any order, and the
+        // bonds all lie along the first axis, so their norms are exact too.
+        let mut sim = Simulation::<f64, 64>::build()
+            .stiffness(1.5)
+            .origin_stiffness(0.5)
+            .mass(2.)
+            .boundary(BoundaryCondition::Periodic)
+            .finish();
+        for (index, pos, vel) in sim.particles_mut() {
+            let q = ((index[0] * 7 + index[1] * 3) % 17) as f64 - 8.;
+            *pos = Vector([q / 8., 0.]);
+            *vel = Vector([q / 4., -q / 8.]);
+        }
+        assert_eq!(sim.par_kinetic_energy(), sim.kinetic_energy());
+        assert_eq!(sim.par_potential_energy(), sim.potential_energy());
+
+        let sim = Simulation::<f64, 64>::build()
+            .stiffness(1.5)
+            .cubic_coupling(0.25)
+            .connectivity(Connectivity::WithDiagonals)
+            .random_init(1., 4)
+            .thermal_init(0.5, 5)
+            .finish();
+        let close = |a: f64, b: f64| (a - b).abs() <= 1e-12 * b.abs();
+        assert!(close(sim.par_kinetic_energy(), sim.kinetic_energy()));
+        assert!(close(sim.par_potential_energy(), sim.potential_energy()));
+
+        #[cfg(feature = "rayon")]
+        for threads in [1, 2, 4, 8] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let (kinetic, potential) =
+                pool.install(|| (sim.par_kinetic_energy(), sim.par_potential_energy()));
+            assert_eq!(kinetic.to_bits(), sim.par_kinetic_energy().to_bits());
+            assert_eq!(potential.to_bits(), sim.par_potential_energy().to_bits());
+        }
+    }
+
+    #[test]
+    fn test_energy_of_single_bond() {
+        let mut sim = Simulation::<f64, 2>::build()
+            .stiffness(2.)
+            .origin_stiffness(0.)
+            .mass(3.)
+            .finish();
+        sim.set_position(Vector([0, 0]), Vector([1., 0.])).unwrap();
+        sim.set_velocity(Vector([1, 1]), Vector([0., 2.])).unwrap();
+
+        assert_eq!(sim.kinetic_energy(), 6.);
+        // (0, 0) is bonded to (1, 0) and (0, 1), each stretched by 1.
+        assert_eq!(sim.potential_energy(), 2.);
+        assert_eq!(sim.total_energy(), 8.);
+    }
+
+    #[test]
+    fn test_max_abs_diff() {
+        let mut sim = Simulation::<f64, 3>::build().random_init(0.5, 1).finish();
+        let copy = sim.clone();
+        assert_eq!(sim.max_abs_diff(&copy), 0.);
+
+        sim.update(1e-2);
+        let diff = sim.max_abs_diff(&copy);
+        assert!(diff > 0.);
+        assert_eq!(copy.max_abs_diff(&sim), diff);
+
+        sim.set_position(Vector([1, 1]), Vector([f64::NAN, 0.]))
+            .unwrap();
+        assert!(sim.max_abs_diff(&copy).is_nan());
+    }
+
+    #[test]
+    fn test_wave_speed_scales_with_sqrt_stiffness() {
+        let speed = |stiffness| {
+            Simulation::<f64, 16>::build()
+                .stiffness(stiffness)
+                .origin_stiffness(0.)
+                .finish()
+                .measure_wave_speed(2e-2)
+        };
+        let (slow, fast) = (speed(1.), speed(4.));
+        assert!((fast / slow - 2.).abs() < 0.05, "{slow} vs {fast}");
+        // Close to the continuum speed of one site per unit time.
+        assert!((slow - 1.).abs() < 0.3, "{slow}");
+
+        let still = Simulation::<f64, 32>::build().stiffness(0.).finish();
+        assert_eq!(still.clone().measure_wave_speed(1e-2), 0.);
+        assert_eq!(
+            Simulation::<f64, 3>::build()
+                .finish()
+                .measure_wave_speed(1e-2),
+            0.
+        );
+    }
+
+    #[test]
+    fn test_displacement_field() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+        assert_eq!(sim.max_displacement(), (Vector([0, 0]), 0.));
+
+        sim.set_position(Vector([0, 1]), Vector([3., 4.])).unwrap();
+        sim.set_position(Vector([2, 0]), Vector([0., -6.])).unwrap();
+        sim.set_position(Vector([1, 2]), Vector([6., 0.])).unwrap();
+
+        let field = sim.displacement_field();
+        assert_eq!(field.len(), 9);
+        assert_eq!(field[..], [0., 5., 0., 0., 0., 6., 6., 0., 0.]);
+        assert_eq!(sim.max_displacement(), (Vector([1, 2]), 6.));
+
+        sim.set_position(Vector([2, 2]), Vector([-7., 1.])).unwrap();
+        let (index, max) = sim.max_displacement();
+        assert_eq!(index, Vector([2, 2]));
+        assert!((max - 50f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_sample_displacement() {
+        let sim = Simulation::<f64, 4>::build().random_init(1., 5).finish();
+        let at = |sim: &Simulation<f64, 4>, i, j| sim.position(Vector([i, j])).unwrap();
+
+        for (index, pos, _) in sim.particles() {
+            assert_eq!(sim.sample_displacement(index.map(|i| i as f64)), pos);
+        }
+        let center = sim.sample_displacement(Vector([1.5, 2.5]));
+        let corners = at(&sim, 1, 2) + at(&sim, 2, 2) + at(&sim, 1, 3) + at(&sim, 2, 3);
+        assert!((center - corners / 4.).norm() < 1e-12);
+
+        let edge = sim.sample_displacement(Vector([3., 0.25]));
+        let expected = at(&sim, 3, 0) * 0.75 + at(&sim, 3, 1) * 0.25;
+        assert!((edge - expected).norm() < 1e-12);
+        assert_eq!(sim.sample_displacement(Vector([-2., 9.])), at(&sim, 0, 3));
+        assert_eq!(sim.sample_displacement(Vector([5., 3.5])), at(&sim, 3, 3));
+
+        // A linear field is reproduced exactly, in three dimensions too.
+        let mut sim = Simulation::<f64, 3, 3>::build().finish();
+        for (index, pos, _) in sim.particles_mut() {
+            *pos = index.map(|i| i as f64) * Vector([1., -2., 0.5]);
+        }
+        let p = Vector([0.3, 1.7, 1.25]);
+        assert!((sim.sample_displacement(p) - p * Vector([1., -2., 0.5])).norm() < 1e-12);
+
+        let mut sim = Simulation::<f64, 1>::build().finish();
+        sim.set_position(Vector([0, 0]), Vector([1., 2.])).unwrap();
+        assert_eq!(
+            sim.sample_displacement(Vector([0.5, -1.])),
+            Vector([1., 2.])
+        );
+    }
+
+    #[test]
+    fn test_strain_of_uniform_shear() {
+        let (shear, stretch) = (0.125, -0.25);
+        let mut sim = Simulation::<f64, 5>::build().finish();
+        for (index, pos, _) in sim.particles_mut() {
+            let [i, j] = index.map(|i| i as f64).0;
+            *pos = Vector([shear * j + 0.5, stretch * i]);
+        }
+
+        let field = sim.strain_field();
+        assert_eq!(field.len(), 25);
+        for gradient in field.iter() {
+            assert_eq!(*gradient, [[0., shear], [stretch, 0.]]);
+        }
+
+        let mut sim = Simulation::<f64, 4, 3>::build().finish();
+        sim.set_position(Vector([1, 2, 3]), Vector([0., 0., 1.]))
+            .unwrap();
+        let field = sim.strain_field();
+        let dz = |index| field[offset::<4, 3>(Vector(index))][2];
+        // One-sided differences at the edges of the grid, central inside.
+        assert_eq!(dz([1, 2, 3]), [0., 0., 1.]);
+        assert_eq!(dz([1, 2, 2]), [0., 0., 0.5]);
+        assert_eq!(dz([2, 2, 3]), [-0.5, 0., 0.]);
+        assert_eq!(dz([0, 2, 3]), [1., 0., 0.]);
+        assert_eq!(dz([1, 1, 3]), [0., 0.5, 0.]);
+        assert_eq!(dz([3, 3, 0]), [0., 0., 0.]);
+
+        let field = Simulation::<f64, 1>::build().finish().strain_field();
+        assert_eq!(field[..], [[[0.; 2]; 2]]);
+    }
+
+    #[test]
+    fn test_virial_stress_of_uniform_strain() {
+        let strained = |strain: f64| {
+            let mut sim = Simulation::<f64, 4>::build().stiffness(2.).finish();
+            for (index, pos, _) in sim.particles_mut() {
+                *pos = index.map(|i| strain * i as f64);
+            }
+            sim.virial_stress()
+        };
+        assert_eq!(strained(0.), [[0.; 2]; 2]);
+
+        // 12 bonds along each axis, each of extension -0.1 and length 0.9.
+        let stress = strained(-0.1);
+        let expected = 12. * 2. * -0.1 * 0.9 / 16.;
+        for (a, row) in stress.iter().enumerate() {
+            for (b, &entry) in row.iter().enumerate() {
+                if a == b {
+                    assert!(entry < 0.);
+                    assert!((entry - expected).abs() < 1e-12, "{stress:?}");
+                } else {
+                    assert_eq!(entry, 0.);
+                }
+            }
+        }
+        assert!(strained(0.1)[1][1] > 0.);
+
+        let mut sim = Simulation::<f64, 3>::build()
+            .stiffness(1.)
+            .boundary(BoundaryCondition::Periodic)
+            .finish();
+        sim.set_position(Vector([1, 1]), Vector([0., 0.5])).unwrap();
+        // Each of the four bonds of the displaced particle adds 0.5² along
+        // the displacement; their lattice offsets cancel in pairs.
+        let stress = sim.virial_stress();
+        let expected = [[0., 0.], [0., 4. * 0.5 * 0.5 / 9.]];
+        for a in 0..2 {
+            for b in 0..2 {
+                assert!((stress[a][b] - expected[a][b]).abs() < 1e-12, "{stress:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_periodic_bonds_counted_once() {
+        let mut sim = Simulation::<f64, 3>::build()
+            .stiffness(1.)
+            .origin_stiffness(0.)
+            .boundary(BoundaryCondition::Periodic)
+            .finish();
+        sim.set_position(Vector([0, 0]), Vector([1., 0.])).unwrap();
+
+        // Four bonds, each stretched by 1.
+        assert_eq!(sim.potential_energy(), 2.);
+    }
+
+    #[test]
+    fn test_gravity_potential() {
+        let mut sim = Simulation::<f64, 1>::build()
+            .origin_stiffness(0.)
+            .mass(2.)
+            .gravity(Vector([0., -9.81]))
+            .finish();
+        sim.set_position(Vector([0, 0]), Vector([0., 1.])).unwrap();
+
+        assert_eq!(sim.potential_energy(), 2. * 9.81);
+    }
+
+    #[test]
+    fn test_energy_conserved_under_verlet() {
+        let mut sim = Simulation::<f64, 6>::build()
+            .stiffness(2.)
+            .origin_stiffness(0.5)
+            .gravity(Vector([0., -1.]))
+            .boundary(BoundaryCondition::Free)
+            .finish();
+        sim.set_position(Vector([1, 2]), Vector([1., -0.5]))
+            .unwrap();
+        sim.set_position(Vector([4, 4]), Vector([-0.5, 0.25]))
+            .unwrap();
+        sim.set_velocity(Vector([0, 5]), Vector([0.25, 0.5]))
+            .unwrap();
+
+        let initial = sim.total_energy();
+        for _ in 0..10_000 {
+            sim.update(1e-3);
+        }
+
+        assert!((sim.total_energy() - initial).abs() < 1e-5 * initial);
+    }
+
+    #[test]
+    fn test_energy_conserved_with_nonlinear_bonds() {
+        let mut sim = Simulation::<f64, 5>::build()
+            .stiffness(2.)
+            .rest_length(0.5)
+            .cubic_coupling(0.75)
+            .connectivity(Connectivity::WithDiagonals)
+            .diagonal_stiffness(0.5)
+            .coupling_radius(2, |distance| 0.125 / distance)
+            .origin_stiffness(0.5)
+            .random_init(0.5, 11)
+            .finish();
+
+        let initial = sim.total_energy();
+        for _ in 0..10_000 {
+            sim.update(1e-3);
+        }
+
+        assert!((sim.total_energy() - initial).abs() < 1e-5 * initial);
+    }
+
+    #[test]
+    fn test_energy_conserved_under_reflective() {
+        let mut chain = Simulation::<f64, 32, 1>::build()
+            .boundary(BoundaryCondition::Reflective)
+            .origin_stiffness(0.5)
+            .random_init(0.5, 5)
+            .finish();
+        let initial = chain.total_energy();
+        chain.run(5000, 1e-3);
+        let drift = (chain.total_energy() - initial).abs();
+        assert!(drift < 1e-5 * initial, "{drift} of {initial}");
+
+        let mut sheet = Simulation::<f64, 6>::build()
+            .boundary(BoundaryCondition::Reflective)
+            .connectivity(Connectivity::WithDiagonals)
+            .diagonal_stiffness(0.5)
+            .cubic_coupling(0.25)
+            .gravity(Vector([0., -1.]))
+            .random_init(0.5, 6)
+            .finish();
+        let initial = sheet.total_energy();
+        sheet.run(5000, 1e-3);
+        let drift = (sheet.total_energy() - initial).abs();
+        assert!(drift < 1e-5 * initial.abs(), "{drift} of {initial}");
+        let parallel = sheet.par_kinetic_energy() + sheet.par_potential_energy();
+        assert!((parallel - sheet.total_energy()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_momentum_and_center_of_mass() {
+        let mut sim = Simulation::<f64, 2>::build().finish();
+        sim.set_mass(Vector([0, 1]), 3.).unwrap();
+        sim.set_position(Vector([0, 1]), Vector([1., 2.])).unwrap();
+        sim.set_velocity(Vector([0, 1]), Vector([1., -1.])).unwrap();
+        sim.set_velocity(Vector([1, 1]), Vector([0.5, 0.])).unwrap();
+
+        assert_eq!(sim.total_momentum(), Vector([3.5, -3.]));
+        assert_eq!(sim.center_of_mass(), Vector([0.5, 1.]));
+    }
+
+    fn kicked_periodic(origin_stiffness: f64) -> Simulation<f64, 5> {
+        let mut sim = Simulation::build()
+            .stiffness(2.)
+            .origin_stiffness(origin_stiffness)
+            .boundary(BoundaryCondition::Periodic)
+            .finish();
+        sim.set_mass(Vector([3, 1]), 4.).unwrap();
+        sim.set_velocity(Vector([0, 0]), Vector([1., 0.5])).unwrap();
+        sim.set_velocity(Vector([3, 1]), Vector([-0.25, 0.]))
+            .unwrap();
+        sim.set_position(Vector([2, 4]), Vector([0.5, 0.])).unwrap();
+        sim
+    }
+
+    #[test]
+    fn test_periodic_momentum_conserved() {
+        let mut sim = kicked_periodic(0.);
+
+        let initial = sim.total_momentum();
+        for _ in 0..1_000 {
+            sim.update(1e-2);
+            let drift = sim.total_momentum() - initial;
+            assert!(drift.norm() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_origin_springs_break_momentum_conservation() {
+        // The origin springs pin the lattice to a fixed frame, which exchanges
+        // momentum with the lattice.
+        let mut sim = kicked_periodic(0.5);
+
+        let initial = sim.total_momentum();
+        sim.run(1_000, 1e-2);
+
+        let drift = sim.total_momentum() - initial;
+        assert!(drift.norm() > 1e-3);
+    }
+}
@@ -0,0 +1,285 @@
+//! Exports of the lattice state for external plotting and analysis tools.
+
+use std::{
+    fmt::Display,
+    io::{self, Write},
+};
+
+use crate::{
+    indexing::{deindex_in, offset_in},
+    Float, Vector,
+};
+
+use super::Simulation;
+
+/// Column names for grid index `axis`: `i`, `j`, `k`, then `i3`, `i4`, ...
+fn index_name(axis: usize) -> String {
+    match axis {
+        0..=2 => ["i", "j", "k"][axis].to_string(),
+        _ => format!("i{axis}"),
+    }
+}
+
+/// Suffixes for component `axis`: `x`, `y`, `z`, then `3`, `4`, ...
+fn component_name(axis: usize) -> String {
+    match axis {
+        0..=2 => ["x", "y", "z"][axis].to_string(),
+        _ => axis.to_string(),
+    }
+}
+
+impl<T: Float + Display, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    /// Writes one CSV row per particle, in row-major order, with its grid
+    /// index, position and velocity. In 2D the columns are
+    /// `i,j,pos_x,pos_y,vel_x,vel_y`.
+    pub fn write_csv(&self, mut w: impl Write) -> io::Result<()> {
+        let header: Vec<String> = (0..DIMS)
+            .map(index_name)
+            .chain((0..DIMS).map(|axis| format!("pos_{}", component_name(axis))))
+            .chain((0..DIMS).map(|axis| format!("vel_{}", component_name(axis))))
+            .collect();
+        writeln!(w, "{}", header.join(","))?;
+
+        for (index, pos, vel) in self.particles() {
+            let row: Vec<String> = index
+                .into_iter()
+                .map(|i| i.to_string())
+                .chain(pos.into_iter().map(|x| x.to_string()))
+                .chain(vel.into_iter().map(|v| v.to_string()))
+                .collect();
+            writeln!(w, "{}", row.join(","))?;
+        }
+        Ok(())
+    }
+
+    /// Writes a legacy-format VTK `STRUCTURED_GRID` with one point per
+    /// particle at its grid index, carrying the `displacement` (position)
+    /// and `velocity` of the particle as point vectors. Missing axes are
+    /// written as zero, so lattices of up to three dimensions are supported.
+    pub fn write_vtk(&self, mut w: impl Write) -> io::Result<()> {
+        if DIMS > 3 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "VTK output supports at most three dimensions",
+            ));
+        }
+        let padded = |v: &[String]| -> String {
+            let mut v = v.to_vec();
+            v.resize(3, "0".to_string());
+            v.join(" ")
+        };
+        let shape = self.state.shape;
+        let points = self.state.pos.len();
+        let scalar = if std::mem::size_of::<T>() <= 4 {
+            "float"
+        } else {
+            "double"
+        };
+
+        writeln!(w, "# vtk DataFile Version 3.0")?;
+        writeln!(w, "rtdriver lattice at t = {}", self.time)?;
+        writeln!(w, "ASCII")?;
+        writeln!(w, "DATASET STRUCTURED_GRID")?;
+        let dimensions: Vec<_> = (0..3)
+            .map(|axis| if axis < DIMS { shape[axis] } else { 1 }.to_string())
+            .collect();
+        writeln!(w, "DIMENSIONS {}", dimensions.join(" "))?;
+
+        // VTK points run with the first axis fastest, the reverse of the
+        // row-major particle buffers.
+        let reversed_shape: Vector<usize, DIMS> = Vector::from_idx(|axis| shape[DIMS - 1 - axis]);
+        let vtk_order = (0..points).map(move |p| {
+            let reversed = deindex_in(reversed_shape, p).unwrap();
+            Vector::from_idx(|axis| reversed[DIMS - 1 - axis])
+        });
+        let components = |v: Vector<T, DIMS>| padded(&v.map(|x| x.to_string()).0);
+
+        writeln!(w, "POINTS {points} {scalar}")?;
+        for indices in vtk_order.clone() {
+            writeln!(w, "{}", padded(&indices.map(|i: usize| i.to_string()).0))?;
+        }
+
+        writeln!(w, "POINT_DATA {points}")?;
+        writeln!(w, "VECTORS displacement {scalar}")?;
+        for indices in vtk_order.clone() {
+            writeln!(
+                w,
+                "{}",
+                components(self.state.pos[offset_in(shape, indices)])
+            )?;
+        }
+        writeln!(w, "VECTORS velocity {scalar}")?;
+        for indices in vtk_order {
+            writeln!(
+                w,
+                "{}",
+                components(self.state.vel[offset_in(shape, indices)])
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    /// Writes the positions as a NumPy `.npy` array (format version 1.0) of
+    /// shape `(SIZE, ..., SIZE, DIMS)`, or the extents of
+    /// [`Simulation::shape`] followed by `DIMS`: one axis per grid index and then one
+    /// for the components, in native byte order. Loading it with
+    /// `numpy.load` gives `pos[i, j, d]` in two dimensions. Fails with
+    /// [`io::ErrorKind::InvalidInput`] if `T` is neither `f32` nor `f64`.
+    pub fn write_npy(&self, mut w: impl Write) -> io::Result<()> {
+        let order = if cfg!(target_endian = "little") {
+            '<'
+        } else {
+            '>'
+        };
+        let descr = match std::mem::size_of::<T>() {
+            4 => format!("{order}f4"),
+            8 => format!("{order}f8"),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "NumPy output supports only f32 and f64",
+                ))
+            }
+        };
+        let shape: Vec<String> = (self.state.shape.into_iter())
+            .chain([DIMS])
+            .map(|extent| extent.to_string())
+            .collect();
+        let mut header = format!(
+            "{{'descr': '{descr}', 'fortran_order': False, 'shape': ({}), }}",
+            shape.join(", ")
+        );
+        // The magic, version and length take 10 bytes; the header is padded
+        // with spaces and a newline so that the data starts 64-byte aligned.
+        let padding = 63 - (10 + header.len()) % 64;
+        header.extend(std::iter::repeat_n(' ', padding));
+        header.push('\n');
+        let header_len = u16::try_from(header.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "NumPy header too long"))?;
+
+        w.write_all(b"\x93NUMPY\x01\x00")?;
+        w.write_all(&header_len.to_le_bytes())?;
+        w.write_all(header.as_bytes())?;
+        w.write_all(bytemuck::cast_slice(&self.state.pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Simulation, Vector};
+
+    #[test]
+    fn test_write_csv() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+        sim.set_position(Vector([0, 0]), Vector([1.5, -2.]))
+            .unwrap();
+        sim.set_velocity(Vector([2, 2]), Vector([0.25, 4.]))
+            .unwrap();
+
+        let mut csv = Vec::new();
+        sim.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "i,j,pos_x,pos_y,vel_x,vel_y");
+        assert_eq!(lines[1], "0,0,1.5,-2,0,0");
+        assert_eq!(lines[2], "0,1,0,0,0,0");
+        assert_eq!(lines[9], "2,2,0,0,0.25,4");
+    }
+
+    fn vtk<const SIZE: usize, const DIMS: usize>(sim: &Simulation<f64, SIZE, DIMS>) -> String {
+        let mut vtk = Vec::new();
+        sim.write_vtk(&mut vtk).unwrap();
+        String::from_utf8(vtk).unwrap()
+    }
+
+    #[test]
+    fn test_write_vtk() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+        sim.set_position(Vector([1, 0]), Vector([0.5, -1.]))
+            .unwrap();
+        sim.set_velocity(Vector([0, 2]), Vector([2., 0.25]))
+            .unwrap();
+        let vtk = vtk(&sim);
+        let lines: Vec<_> = vtk.lines().collect();
+
+        assert_eq!(lines[0], "# vtk DataFile Version 3.0");
+        assert_eq!(lines[3], "DATASET STRUCTURED_GRID");
+        assert_eq!(lines[4], "DIMENSIONS 3 3 1");
+        assert_eq!(lines[5], "POINTS 9 double");
+        // The first axis runs fastest.
+        assert_eq!(&lines[6..9], ["0 0 0", "1 0 0", "2 0 0"]);
+        assert_eq!(lines[15], "POINT_DATA 9");
+        assert_eq!(lines[16], "VECTORS displacement double");
+        assert_eq!(lines[18], "0.5 -1 0");
+        assert_eq!(lines[26], "VECTORS velocity double");
+        assert_eq!(lines[33], "2 0.25 0");
+        assert_eq!(lines.len(), 36);
+    }
+
+    #[test]
+    fn test_write_npy() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+        sim.set_position(Vector([2, 1]), Vector([0.5, -1.]))
+            .unwrap();
+        let mut npy = Vec::new();
+        sim.write_npy(&mut npy).unwrap();
+
+        assert_eq!(&npy[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&npy[10..10 + header_len]).unwrap();
+        assert!(
+            header.starts_with("{'descr': '<f8', 'fortran_order': False, 'shape': (3, 3, 2), }")
+        );
+        assert!(header.ends_with(" \n"));
+
+        let data = &npy[10 + header_len..];
+        assert_eq!(data.len(), 9 * 2 * 8);
+        let at = |k: usize| f64::from_le_bytes(data[k * 8..k * 8 + 8].try_into().unwrap());
+        assert_eq!([at(14), at(15)], [0.5, -1.]);
+
+        let sim = Simulation::<f32, 2, 3>::build().finish();
+        let mut npy = Vec::new();
+        sim.write_npy(&mut npy).unwrap();
+        let header_len = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+        let header = std::str::from_utf8(&npy[10..10 + header_len]).unwrap();
+        assert!(header.contains("'descr': '<f4'"));
+        assert!(header.contains("'shape': (2, 2, 2, 3)"));
+        assert_eq!(npy.len(), 128 + 8 * 3 * 4);
+    }
+
+    #[test]
+    fn test_write_vtk_3d() {
+        let sim = Simulation::<f64, 10, 3>::build().finish();
+        let vtk = vtk(&sim);
+
+        assert!(vtk.contains("\nDIMENSIONS 10 10 10\n"));
+        assert!(vtk.contains("\nPOINT_DATA 1000\n"));
+    }
+
+    #[test]
+    fn test_write_vtk_rejects_four_dimensions() {
+        let sim = Simulation::<f64, 2, 4>::build().finish();
+        let mut vtk = Vec::new();
+        assert!(sim.write_vtk(&mut vtk).is_err());
+    }
+
+    #[test]
+    fn test_write_csv_3d_header() {
+        let sim = Simulation::<f32, 2, 3>::build().finish();
+
+        let mut csv = Vec::new();
+        sim.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+
+        assert_eq!(
+            csv.lines().next(),
+            Some("i,j,k,pos_x,pos_y,pos_z,vel_x,vel_y,vel_z")
+        );
+        assert_eq!(csv.lines().count(), 9);
+    }
+}
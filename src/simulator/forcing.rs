@@ -0,0 +1,255 @@
+use std::{fmt, sync::Arc};
+
+use crate::{error::OutOfBounds, indexing::deindex_in, Float, Vector};
+
+use super::Simulation;
+
+type ForceFn<T, const DIMS: usize> =
+    dyn Fn(Vector<usize, DIMS>, T) -> Vector<T, DIMS> + Send + Sync;
+
+/// An optional user-supplied external force, evaluated per particle.
+#[derive(Clone)]
+pub(super) struct ForceField<T, const DIMS: usize>(Option<Arc<ForceFn<T, DIMS>>>);
+
+impl<T, const DIMS: usize> ForceField<T, DIMS> {
+    pub(super) fn none() -> Self {
+        ForceField(None)
+    }
+
+    #[cfg(feature = "simd")]
+    pub(super) fn is_set(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// The same field on a grid whose index zero is `lo` on this one.
+    pub(super) fn shifted(&self, lo: Vector<usize, DIMS>) -> Self
+    where
+        T: 'static,
+    {
+        ForceField(self.0.clone().map(|field| {
+            Arc::new(move |index, time| field(index + lo, time)) as Arc<ForceFn<T, DIMS>>
+        }))
+    }
+
+    /// The force on the particle at `index` at simulated time `time`, if a
+    /// field is set.
+    pub(super) fn force(&self, index: Vector<usize, DIMS>, time: T) -> Option<Vector<T, DIMS>> {
+        self.0.as_ref().map(|field| field(index, time))
+    }
+}
+
+impl<T, const DIMS: usize> fmt::Debug for ForceField<T, DIMS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(_) => f.write_str("ForceField(..)"),
+            None => f.write_str("ForceField(None)"),
+        }
+    }
+}
+
+/// Two fields are equal when they are both unset or share the same closure.
+impl<T, const DIMS: usize> PartialEq for ForceField<T, DIMS> {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// One face of the grid: the particles whose index along an axis is `0`
+/// ([`Edge::Low`]) or one less than the extent of the axis ([`Edge::High`]). In 2D, `Low(1)` is the
+/// left edge `j = 0` and `Low(0)` the top edge `i = 0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Low(usize),
+    High(usize),
+}
+
+/// An edge whose positions follow a prescribed oscillation.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct DrivenEdge<T, const DIMS: usize> {
+    /// Flat offsets of the edge particles, pinned ones excluded.
+    particles: Box<[usize]>,
+    amplitude: Vector<T, DIMS>,
+    frequency: T,
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    /// Drives the particles on `edge`: from now on, after every step their
+    /// positions are set to `amplitude * sin(2π * frequency * time)` and
+    /// their velocities to its time derivative, and the interior responds
+    /// through the coupling. Pinned particles stay put. Replaces any earlier
+    /// drive, and fails if the axis of `edge` is not less than `DIMS`. Like
+    /// force fields, drives are not saved in checkpoints.
+    pub fn set_driven_edge(
+        &mut self,
+        edge: Edge,
+        amplitude: Vector<T, DIMS>,
+        frequency: T,
+    ) -> Result<(), OutOfBounds> {
+        let (axis, high) = match edge {
+            Edge::Low(axis) => (axis, false),
+            Edge::High(axis) => (axis, true),
+        };
+        if axis >= DIMS {
+            return Err(OutOfBounds);
+        }
+        let shape = self.state.shape;
+        // An axis of extent zero has no edge, and so nothing to drive.
+        let at = if high {
+            shape[axis].checked_sub(1)
+        } else {
+            Some(0)
+        };
+        let particles = (0..self.state.pos.len())
+            .filter(|&k| !self.state.pinned[k] && Some(deindex_in(shape, k).unwrap()[axis]) == at)
+            .collect();
+        self.driven = Some(DrivenEdge {
+            particles,
+            amplitude,
+            frequency,
+        });
+        self.drive_edge();
+        Ok(())
+    }
+
+    /// Stops the drive set by [`Simulation::set_driven_edge`], leaving the
+    /// edge to move freely from where it is.
+    pub fn clear_driven_edge(&mut self) {
+        self.driven = None;
+    }
+
+    /// Moves the driven edge, if any, to its prescribed state at the current
+    /// time.
+    pub(super) fn drive_edge(&mut self) {
+        let Some(driven) = &self.driven else {
+            return;
+        };
+        let omega = T::from(std::f64::consts::TAU).unwrap() * driven.frequency;
+        let phase = omega * self.time;
+        let pos = driven.amplitude.map(|a| a * phase.sin());
+        let vel = driven.amplitude.map(|a| a * omega * phase.cos());
+        for &k in driven.particles.iter() {
+            self.state.pos[k] = pos;
+            self.state.vel[k] = vel;
+        }
+        // The interior's accelerations depend on the positions just set.
+        self.acc_stale = true;
+    }
+
+    /// Adds an external force to every particle. The closure receives the
+    /// particle's grid index and the current simulated time and returns the
+    /// force on that particle, which is divided by its mass like any other.
+    pub fn set_force_field(
+        &mut self,
+        f: impl Fn(Vector<usize, DIMS>, T) -> Vector<T, DIMS> + Send + Sync + 'static,
+    ) {
+        self.force_field = ForceField(Some(Arc::new(f)));
+        self.acc_stale = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Edge;
+    use crate::{error::OutOfBounds, indexing::offset, Simulation, Vector};
+
+    #[test]
+    fn test_force_field_sees_index_and_time() {
+        let mut sim = Simulation::<f64, 3>::build().origin_stiffness(0.).finish();
+        sim.set_force_field(|index, time| {
+            if index == Vector([2, 1]) {
+                Vector([time, 1.])
+            } else {
+                Vector::zero()
+            }
+        });
+        sim.refresh_acc();
+
+        assert_eq!(
+            sim.state.acc[offset::<3, 2>(Vector([2, 1]))],
+            Vector([0., 1.])
+        );
+        assert_eq!(
+            sim.state.acc[offset::<3, 2>(Vector([1, 2]))],
+            Vector::zero()
+        );
+
+        sim.update(0.5);
+        assert!(sim.state.acc[offset::<3, 2>(Vector([2, 1]))][0] > 0.);
+    }
+
+    #[test]
+    fn test_driven_edge_launches_wave() {
+        let mut sim = Simulation::<f64, 24>::build()
+            .stiffness(1.)
+            .origin_stiffness(0.)
+            .finish();
+        sim.set_driven_edge(Edge::Low(1), Vector([0., 0.5]), 0.05)
+            .unwrap();
+        let transverse = |sim: &Simulation<f64, 24>, j| sim.position(Vector([12, j])).unwrap()[1];
+
+        // The front moves one site per unit time.
+        sim.run(200, 5e-2);
+        let phase = std::f64::consts::TAU * 0.05 * sim.time();
+        assert!((transverse(&sim, 0) - 0.5 * phase.sin()).abs() < 1e-12);
+        assert!(transverse(&sim, 4).abs() > 1e-2);
+        assert!(transverse(&sim, 20).abs() < 1e-4);
+
+        sim.run(200, 5e-2);
+        assert!(transverse(&sim, 14).abs() > 1e-2);
+        // A plane wave: every row moves alike.
+        for i in 0..24 {
+            assert_eq!(
+                sim.position(Vector([i, 14])),
+                sim.position(Vector([12, 14]))
+            );
+        }
+
+        assert_eq!(
+            sim.set_driven_edge(Edge::High(2), Vector::zero(), 1.),
+            Err(OutOfBounds)
+        );
+        sim.clear_driven_edge();
+        let edge = sim.position(Vector([0, 0])).unwrap();
+        sim.run(10, 5e-2);
+        assert_ne!(sim.position(Vector([0, 0])).unwrap(), edge);
+
+        let mut empty = Simulation::<f64, 4>::build().shape(Vector([4, 0])).finish();
+        for edge in [Edge::Low(1), Edge::High(1), Edge::High(0)] {
+            assert_eq!(empty.set_driven_edge(edge, Vector([0., 0.5]), 1.), Ok(()));
+            empty.run(2, 5e-2);
+        }
+    }
+
+    #[test]
+    fn test_sinusoidal_drive_reaches_steady_state() {
+        let (k, c, amplitude, omega) = (1., 0.2, 0.5, 2.);
+
+        let mut sim = Simulation::<f64, 1>::build()
+            .origin_stiffness(k)
+            .damping(c)
+            .finish();
+        sim.set_force_field(move |_, time| Vector([amplitude * (omega * time).sin(), 0.]));
+
+        let dt = 1e-2;
+        for _ in 0..15_000 {
+            sim.update(dt);
+        }
+
+        let mut peak: f64 = 0.;
+        // A few periods of the drive, long after the transient has decayed.
+        for _ in 0..1_000 {
+            sim.update(dt);
+            peak = peak.max(sim.state.pos[0][0].abs());
+        }
+
+        let expected = amplitude / ((k - omega * omega).powi(2) + (c * omega).powi(2)).sqrt();
+        assert!(
+            (peak - expected).abs() < 1e-2 * expected,
+            "{peak} vs {expected}"
+        );
+    }
+}
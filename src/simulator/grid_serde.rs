@@ -0,0 +1,55 @@
+//! Checks that the flat particle buffers of [`SimulationState`] hold exactly
+//! one element per particle of its shape when deserialized.
+//!
+//! The derived implementations are the inherent `serialize` and
+//! `deserialize` of `SimulationState`, which the trait implementations here
+//! wrap.
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{indexing::shape_len, Float, Vector};
+
+use super::SimulationState;
+
+/// The shape of a state saved without one: `SIZE` along every axis.
+pub(super) fn square<const SIZE: usize, const DIMS: usize>() -> Vector<usize, DIMS> {
+    Vector::broadcast(SIZE)
+}
+
+impl<T, const SIZE: usize, const DIMS: usize> Serialize for SimulationState<T, SIZE, DIMS>
+where
+    T: Float + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SimulationState::serialize(self, serializer)
+    }
+}
+
+impl<'de, T, const SIZE: usize, const DIMS: usize> Deserialize<'de>
+    for SimulationState<T, SIZE, DIMS>
+where
+    T: Float + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = SimulationState::deserialize(deserializer)?;
+        let len = shape_len(state.shape)
+            .ok_or_else(|| D::Error::custom("grid shape has too many particles"))?;
+        let lengths = [
+            state.mass.len(),
+            state.pos.len(),
+            state.vel.len(),
+            state.acc.len(),
+            state.pinned.len(),
+        ];
+        let field = state.bond_stiffness.as_ref().map(|field| field.len());
+        for actual in lengths.into_iter().chain(field) {
+            if actual != len {
+                return Err(D::Error::invalid_length(
+                    actual,
+                    &"one element per particle",
+                ));
+            }
+        }
+        Ok(state)
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{error::OutOfBounds, indexing::checked_offset_in, Float, Vector};
+
+use super::Simulation;
+
+/// The probed particles after one recorded step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot<T, const DIMS: usize = { crate::DIMS }> {
+    /// Simulated time at the end of the step.
+    pub time: T,
+    /// Position of every probe, in the order given to [`Simulation::record`].
+    pub pos: Box<[Vector<T, DIMS>]>,
+    /// Velocity of every probe, in the same order.
+    pub vel: Box<[Vector<T, DIMS>]>,
+}
+
+/// Probes registered by [`Simulation::record`] and what they recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct History<T, const DIMS: usize> {
+    /// Flat offsets of the probed particles.
+    probes: Box<[usize]>,
+    stride: usize,
+    /// Steps taken since the probes were registered.
+    steps: usize,
+    snapshots: Vec<Snapshot<T, DIMS>>,
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    /// Starts recording the position and velocity of the particles at
+    /// `probes` after every `stride` steps, one being every step and zero
+    /// being treated as one. Replaces any earlier probes and discards what
+    /// they recorded. Fails, leaving recording unchanged, if any index lies
+    /// outside the grid.
+    pub fn record(
+        &mut self,
+        probes: &[Vector<usize, DIMS>],
+        stride: usize,
+    ) -> Result<(), OutOfBounds> {
+        let probes = probes
+            .iter()
+            .map(|&index| checked_offset_in(self.state.shape, index).ok_or(OutOfBounds))
+            .collect::<Result<_, _>>()?;
+        self.history = Some(History {
+            probes,
+            stride: stride.max(1),
+            steps: 0,
+            snapshots: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Stops recording and discards the history.
+    pub fn stop_recording(&mut self) {
+        self.history = None;
+    }
+
+    /// The snapshots taken since [`Simulation::record`], oldest first.
+    pub fn history(&self) -> &[Snapshot<T, DIMS>] {
+        self.history
+            .as_ref()
+            .map_or(&[], |history| &history.snapshots)
+    }
+
+    /// Counts a step and takes a snapshot if one is due.
+    pub(super) fn sample_history(&mut self) {
+        let Some(history) = &mut self.history else {
+            return;
+        };
+        history.steps += 1;
+        if history.steps % history.stride != 0 {
+            return;
+        }
+        let state = &self.state;
+        history.snapshots.push(Snapshot {
+            time: self.time,
+            pos: history.probes.iter().map(|&k| state.pos[k]).collect(),
+            vel: history.probes.iter().map(|&k| state.vel[k]).collect(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::OutOfBounds, Simulation, Vector};
+
+    #[test]
+    fn test_record_with_stride() {
+        let mut sim = Simulation::<f64, 4>::build().finish();
+        sim.set_position(Vector([1, 2]), Vector([0.5, 0.])).unwrap();
+        assert!(sim.history().is_empty());
+
+        sim.record(&[Vector([1, 2])], 10).unwrap();
+        sim.run(100, 1e-2);
+
+        let history = sim.history();
+        assert_eq!(history.len(), 10);
+        assert!((history[0].time - 0.1).abs() < 1e-12);
+        assert!((history[9].time - 1.).abs() < 1e-12);
+        let last = &history[9];
+        assert_eq!(last.pos[..], [sim.position(Vector([1, 2])).unwrap()]);
+        assert_eq!(last.vel[..], [sim.velocity(Vector([1, 2])).unwrap()]);
+        assert_ne!(history[0].pos, history[9].pos);
+
+        sim.record(&[Vector([0, 0]), Vector([3, 3])], 1).unwrap();
+        sim.run(3, 1e-2);
+        assert_eq!(sim.history().len(), 3);
+        assert_eq!(sim.history()[0].pos.len(), 2);
+
+        assert_eq!(sim.record(&[Vector([4, 0])], 1), Err(OutOfBounds));
+        assert_eq!(sim.history().len(), 3);
+
+        sim.update_adaptive(1e-2, 1.);
+        assert_eq!(sim.history().len(), 4);
+
+        sim.stop_recording();
+        sim.run(3, 1e-2);
+        assert!(sim.history().is_empty());
+    }
+}
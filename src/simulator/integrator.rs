@@ -0,0 +1,534 @@
+use crate::{indexing::deindex_in, Float, Vector};
+
+use super::Simulation;
+
+/// Time-stepping scheme used by [`Simulation::update`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Integrator {
+    /// Explicit (forward) Euler. Not symplectic, energy grows every step.
+    Euler,
+    /// Semi-implicit Euler: kick the velocity, then drift the position.
+    SymplecticEuler,
+    /// Kick-drift-kick velocity Verlet.
+    #[default]
+    VelocityVerlet,
+    /// Leapfrog with velocities stored half a step ahead of positions. See
+    /// [`Simulation::prime_leapfrog`] for the half-step convention.
+    Leapfrog,
+    /// Classical fourth-order Runge–Kutta. Not symplectic, but its local
+    /// error is `O(dt^5)`, which makes it useful for accuracy studies.
+    RungeKutta4,
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    pub(super) fn step_euler(&mut self, dt: T) {
+        self.refresh_acc();
+        self.drift(dt);
+        self.kick(dt);
+        self.time += dt;
+    }
+
+    pub(super) fn step_symplectic_euler(&mut self, dt: T) {
+        self.refresh_acc();
+        self.kick(dt);
+        self.drift(dt);
+        self.time += dt;
+    }
+
+    pub(super) fn step_velocity_verlet(&mut self, dt: T) {
+        let half_dt = dt / (T::one() + T::one());
+
+        self.kick(half_dt);
+        self.drift(dt);
+        self.time += dt;
+        self.refresh_acc();
+        self.kick(half_dt);
+    }
+
+    /// Takes a single step whose size is chosen by step doubling and returns
+    /// the `dt` that was actually used.
+    ///
+    /// The candidate step is `dt_hint`, but never more than twice the step
+    /// accepted by the previous call. The local truncation error is estimated
+    /// as the largest difference in any position component between one full
+    /// step and two half steps; while it exceeds `tol` the candidate is
+    /// halved. The step is never shrunk below the builder's `min_dt`, and a
+    /// step at that floor is taken even if it misses `tol`. The state is
+    /// advanced with the two half steps of the accepted candidate, which
+    /// count as a single step for [`Simulation::record`].
+    pub fn update_adaptive(&mut self, dt_hint: T, tol: T) -> T {
+        let two = T::one() + T::one();
+        // Kept out of the trial copies, which would otherwise clone it.
+        let history = self.history.take();
+        let steps = self.steps;
+
+        let mut dt = match self.last_adaptive_dt {
+            Some(last) => dt_hint.min(last * two),
+            None => dt_hint,
+        };
+        dt = dt.max(self.min_dt);
+
+        loop {
+            let mut full = self.clone();
+            full.update(dt);
+
+            let mut halves = self.clone();
+            halves.update(dt / two);
+            halves.update(dt / two);
+
+            let next_dt = dt / two;
+            if halves.max_abs_diff(&full) <= tol || next_dt < self.min_dt {
+                *self = halves;
+                self.last_adaptive_dt = Some(dt);
+                self.steps = steps + 1;
+                self.history = history;
+                self.sample_history();
+                return dt;
+            }
+            dt = next_dt;
+        }
+    }
+
+    /// Estimates the largest `dt` for which [`Integrator::SymplecticEuler`],
+    /// [`Integrator::VelocityVerlet`] and [`Integrator::Leapfrog`] stay
+    /// stable, `2 / omega_max`, with `omega_max` the fastest frequency of
+    /// the linearized lattice.
+    ///
+    /// `omega_max^2` is bounded by the largest row sum of the stiffness
+    /// matrix over mass: the origin stiffness plus twice the spring constant
+    /// of every bond of a particle, divided by its mass. The bound is exact
+    /// for a uniform periodic lattice of even side and pessimistic
+    /// otherwise. Rest lengths, cubic terms and damping are not taken into
+    /// account, so this is a guide rather than a guarantee. Pinned and
+    /// clamped particles do not move and are skipped. Infinite if nothing
+    /// can oscillate.
+    pub fn max_stable_dt(&self) -> T {
+        let state = &self.state;
+        let two = T::one() + T::one();
+
+        let mut omega_sq = T::zero();
+        for k in 0..state.pos.len() {
+            let here = deindex_in(state.shape, k).unwrap();
+            if state.pinned[k] || state.boundary.is_clamped(state.shape, here) {
+                continue;
+            }
+            let mut row = state.origin_stiffness.abs();
+            for (n, kind) in self.neighbors.bonds(k) {
+                let mut stiffness = state.spring_constant(kind).abs();
+                if let Some(field) = &state.bond_stiffness {
+                    stiffness = stiffness * (field[k] + field[n]).abs() / two;
+                }
+                row += two * stiffness;
+            }
+            omega_sq = omega_sq.max(row / state.mass[k]);
+        }
+        two / omega_sq.sqrt()
+    }
+
+    /// The angular frequency `sqrt(origin_stiffness / mass)` at which a
+    /// particle oscillates about its origin when the coupling is ignored.
+    /// With masses set per particle, the lightest particle that is neither
+    /// pinned nor clamped sets it, giving the fastest such oscillation. Zero
+    /// if no particle can move.
+    pub fn origin_frequency(&self) -> T {
+        let state = &self.state;
+        let mut mass = T::infinity();
+        for (k, &m) in state.mass.iter().enumerate() {
+            let here = deindex_in(state.shape, k).unwrap();
+            if !(state.pinned[k] || state.boundary.is_clamped(state.shape, here)) {
+                mass = mass.min(m);
+            }
+        }
+        (state.origin_stiffness / mass).sqrt()
+    }
+
+    /// The period `2π / ω` of [`Simulation::origin_frequency`], e.g. to pick
+    /// `dt` as a fraction of it. Infinite without an origin spring.
+    pub fn origin_period(&self) -> T {
+        T::from(std::f64::consts::TAU).unwrap() / self.origin_frequency()
+    }
+
+    /// Shifts the velocities forward by half a step, turning the synchronous
+    /// state `(pos(t), vel(t))` into the staggered state
+    /// `(pos(t), vel(t + dt / 2))` that [`Integrator::Leapfrog`] expects.
+    ///
+    /// Call this once, with the `dt` that will be used for stepping, before
+    /// the first leapfrog `update`. Afterwards the stored velocities always
+    /// lead the positions by `dt / 2`, so energy diagnostics computed
+    /// directly from them carry an `O(dt)` offset. The synchronous velocity
+    /// at the current positions is `vel - acc * dt / 2`.
+    pub fn prime_leapfrog(&mut self, dt: T) {
+        self.refresh_acc();
+        self.kick(dt / (T::one() + T::one()));
+    }
+
+    pub(super) fn step_leapfrog(&mut self, dt: T) {
+        self.drift(dt);
+        self.time += dt;
+        self.refresh_acc();
+        self.kick(dt);
+    }
+
+    pub(super) fn step_rk4(&mut self, dt: T) {
+        let two = T::one() + T::one();
+        let half_dt = dt / two;
+        let sixth_dt = dt / (two + two + two);
+
+        let Self {
+            state,
+            force_field,
+            neighbors,
+            threads,
+            time,
+            tmp_acc,
+            tmp_pos,
+            tmp_vel,
+            rk4_sums,
+            ..
+        } = self;
+        // The back buffers hold the stage points until the end of the step,
+        // so that it allocates nothing.
+        let (stage_pos, stage_vel, stage_acc) = (tmp_pos, tmp_vel, tmp_acc);
+        stage_pos.copy_from_slice(&state.pos);
+        stage_vel.copy_from_slice(&state.vel);
+        // Weighted sums of the stage derivatives of `pos` and `vel`.
+        rk4_sums.clear();
+        rk4_sums.resize(state.pos.len(), [Vector::zero(); 2]);
+
+        // Each stage is evaluated at `time + offset`, then the next stage
+        // point is placed `step` away from the start of the step.
+        let stages = [
+            (T::one(), T::zero(), half_dt),
+            (two, half_dt, half_dt),
+            (two, half_dt, dt),
+            (T::one(), dt, T::zero()),
+        ];
+        for (weight, offset, step) in stages {
+            let stage_time = *time + offset;
+            state.accelerations(
+                force_field,
+                neighbors,
+                *threads,
+                stage_time,
+                stage_pos,
+                stage_vel,
+                stage_acc,
+            );
+
+            for k in 0..state.pos.len() {
+                let [sum_vel, sum_acc] = &mut rk4_sums[k];
+                *sum_vel += stage_vel[k] * weight;
+                *sum_acc += stage_acc[k] * weight;
+
+                stage_pos[k] = state.pos[k] + stage_vel[k] * step;
+                stage_vel[k] = state.vel[k] + stage_acc[k] * step;
+            }
+        }
+
+        for (k, &[sum_vel, sum_acc]) in rk4_sums.iter().enumerate() {
+            stage_pos[k] = state.pos[k] + sum_vel * sixth_dt;
+            stage_vel[k] = state.vel[k] + sum_acc * sixth_dt;
+        }
+        std::mem::swap(stage_pos, &mut state.pos);
+        std::mem::swap(stage_vel, &mut state.vel);
+
+        *time += dt;
+        self.refresh_acc();
+    }
+
+    /// `vel += acc * dt`, through the back buffer.
+    fn kick(&mut self, dt: T) {
+        let state = &mut self.state;
+        for ((new, &vel), &acc) in self.tmp_vel.iter_mut().zip(&state.vel).zip(&state.acc) {
+            *new = vel + acc * dt;
+        }
+        std::mem::swap(&mut self.tmp_vel, &mut state.vel);
+    }
+
+    /// `pos += vel * dt`, through the back buffer.
+    fn drift(&mut self, dt: T) {
+        let state = &mut self.state;
+        for ((new, &pos), &vel) in self.tmp_pos.iter_mut().zip(&state.pos).zip(&state.vel) {
+            *new = pos + vel * dt;
+        }
+        std::mem::swap(&mut self.tmp_pos, &mut state.pos);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Integrator;
+    use crate::{indexing::offset, BoundaryCondition, Simulation, Vector};
+
+    fn displaced(integrator: Integrator) -> Simulation<f64, 4> {
+        let mut sim = Simulation::build()
+            .stiffness(0.)
+            .origin_stiffness(1.)
+            .integrator(integrator)
+            .finish();
+        sim.state.pos[offset::<4, 2>(Vector([1, 2]))] = Vector([1., -0.5]);
+        sim.state.vel[offset::<4, 2>(Vector([3, 0]))] = Vector([0.25, 0.5]);
+        sim.refresh_acc();
+        if integrator == Integrator::Leapfrog {
+            sim.prime_leapfrog(1e-2);
+        }
+        sim
+    }
+
+    fn energy_drift(integrator: Integrator) -> f64 {
+        let mut sim = displaced(integrator);
+        let initial = sim.total_energy();
+        for _ in 0..10_000 {
+            sim.update(1e-2);
+        }
+        (sim.total_energy() - initial).abs() / initial
+    }
+
+    const ALL: [Integrator; 5] = [
+        Integrator::Euler,
+        Integrator::SymplecticEuler,
+        Integrator::VelocityVerlet,
+        Integrator::Leapfrog,
+        Integrator::RungeKutta4,
+    ];
+
+    #[test]
+    fn test_default_is_velocity_verlet() {
+        let sim = Simulation::<f32, 2>::build().finish();
+        assert_eq!(sim.integrator, Integrator::VelocityVerlet);
+    }
+
+    #[test]
+    fn test_integrators_move_displaced_particle() {
+        for integrator in ALL {
+            let mut sim = displaced(integrator);
+            let k = offset::<4, 2>(Vector([1, 2]));
+            let initial = sim.state.pos[k];
+            for _ in 0..10 {
+                sim.update(1e-2);
+            }
+            assert_ne!(sim.state.pos[k], initial, "{integrator:?}");
+            assert_ne!(sim.state.vel[k], Vector::zero(), "{integrator:?}");
+        }
+    }
+
+    #[test]
+    fn test_buffered_step_matches_in_place() {
+        let mut buffered = displaced(Integrator::VelocityVerlet);
+        let mut in_place = buffered.clone();
+        let (dt, half_dt) = (1e-2, 5e-3);
+        for _ in 0..100 {
+            buffered.update(dt);
+
+            let state = &mut in_place.state;
+            for k in 0..state.pos.len() {
+                state.vel[k] += state.acc[k] * half_dt;
+                state.pos[k] += state.vel[k] * dt;
+            }
+            in_place.time += dt;
+            in_place.refresh_acc();
+            let state = &mut in_place.state;
+            for k in 0..state.pos.len() {
+                state.vel[k] += state.acc[k] * half_dt;
+            }
+        }
+        assert_eq!(buffered.state.pos, in_place.state.pos);
+        assert_eq!(buffered.state.vel, in_place.state.vel);
+        assert_eq!(buffered.time, in_place.time);
+    }
+
+    #[test]
+    fn test_symplectic_integrators_conserve_energy() {
+        let euler_drift = energy_drift(Integrator::Euler);
+        assert!(euler_drift > 1., "euler drift {euler_drift}");
+
+        for integrator in [
+            Integrator::SymplecticEuler,
+            Integrator::VelocityVerlet,
+            Integrator::Leapfrog,
+        ] {
+            let drift = energy_drift(integrator);
+            assert!(drift * 1e2 < euler_drift, "{integrator:?} drift {drift}");
+        }
+    }
+
+    #[test]
+    fn test_verlet_energy_drift() {
+        let verlet_drift = energy_drift(Integrator::VelocityVerlet);
+        let euler_drift = energy_drift(Integrator::Euler);
+
+        assert!(verlet_drift < 1e-4, "verlet drift {verlet_drift}");
+        assert!(verlet_drift * 1e4 < euler_drift);
+    }
+
+    /// Error at `t = 1` of a unit oscillator released from rest at `x = 1`,
+    /// whose exact trajectory is `cos(t)`.
+    fn rk4_error(steps: usize) -> f64 {
+        let mut sim = Simulation::<f64, 1>::build()
+            .stiffness(0.)
+            .origin_stiffness(1.)
+            .integrator(Integrator::RungeKutta4)
+            .finish();
+        sim.state.pos[0] = Vector([1., 0.]);
+
+        let dt = 1. / steps as f64;
+        for _ in 0..steps {
+            sim.update(dt);
+        }
+
+        (sim.state.pos[0][0] - 1f64.cos()).abs()
+    }
+
+    #[test]
+    fn test_rk4_fourth_order_convergence() {
+        let errors = [10, 20, 40].map(rk4_error);
+
+        for pair in errors.windows(2) {
+            let order = (pair[0] / pair[1]).log2();
+            assert!((3.8..4.2).contains(&order), "observed order {order}");
+        }
+    }
+
+    #[test]
+    fn test_leapfrog_preserves_phase_space_area() {
+        let dt = 1e-2;
+        let mut sim = Simulation::<f64, 2>::build()
+            .stiffness(0.)
+            .origin_stiffness(1.)
+            .integrator(Integrator::Leapfrog)
+            .finish();
+
+        // Three uncoupled oscillators span a triangle in the (x, v) plane.
+        sim.state.pos[0] = Vector([1., 0.]);
+        sim.state.pos[offset::<2, 2>(Vector([0, 1]))] = Vector([0., 0.]);
+        sim.state.vel[offset::<2, 2>(Vector([0, 1]))] = Vector([0.5, 0.]);
+        sim.state.pos[offset::<2, 2>(Vector([1, 0]))] = Vector([-0.25, 0.]);
+        sim.state.vel[offset::<2, 2>(Vector([1, 0]))] = Vector([-0.75, 0.]);
+        sim.prime_leapfrog(dt);
+
+        let area = |sim: &Simulation<f64, 2>| {
+            let [a, b, c] = [0, 1, 2].map(|k| (sim.state.pos[k][0], sim.state.vel[k][0]));
+            0.5 * ((b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)).abs()
+        };
+
+        let initial = area(&sim);
+        // Roughly 100 periods of the unit oscillator.
+        for _ in 0..62_832 {
+            sim.update(dt);
+        }
+
+        assert!((area(&sim) - initial).abs() < 1e-9 * initial);
+    }
+
+    fn stiff() -> Simulation<f64, 3> {
+        let mut sim = Simulation::build()
+            .stiffness(0.)
+            .origin_stiffness(1e4)
+            .min_dt(1e-6)
+            .finish();
+        sim.state.pos[offset::<3, 2>(Vector([1, 1]))] = Vector([1., 0.]);
+        sim.refresh_acc();
+        sim
+    }
+
+    #[test]
+    fn test_adaptive_shrinks_stiff_step() {
+        let mut sim = stiff();
+        let mut reference = sim.clone();
+
+        let dt = sim.update_adaptive(0.1, 1e-6);
+
+        assert!(dt < 0.1);
+        assert!(dt >= 1e-6);
+
+        reference.update(dt / 2.);
+        reference.update(dt / 2.);
+        assert_eq!(sim.state.pos, reference.state.pos);
+    }
+
+    #[test]
+    fn test_adaptive_growth_is_clamped() {
+        let mut sim = stiff();
+
+        let first = sim.update_adaptive(0.1, 1e-6);
+        let second = sim.update_adaptive(1e3, 1.);
+
+        assert!(second <= 2. * first);
+    }
+
+    #[test]
+    fn test_origin_period_matches_oscillation() {
+        let mut sim = Simulation::<f64, 1>::build()
+            .origin_stiffness(8.)
+            .mass(2.)
+            .finish();
+        assert_eq!(sim.origin_frequency(), 2.);
+        assert_eq!(sim.origin_period(), std::f64::consts::PI);
+
+        sim.set_position(Vector([0, 0]), Vector([1., 0.])).unwrap();
+        let dt = sim.origin_period() / 1000.;
+        let mut crossings = Vec::new();
+        let mut x = 1.;
+        while crossings.len() < 5 {
+            sim.update(dt);
+            let next = sim.state.pos[0][0];
+            if (x < 0.) != (next < 0.) {
+                // Interpolate to where the step crossed zero.
+                crossings.push(sim.time - dt * next / (next - x));
+            }
+            x = next;
+        }
+        // Two crossings per period.
+        let period = (crossings[4] - crossings[0]) / 2.;
+        assert!((period - sim.origin_period()).abs() < 1e-4, "{period}");
+
+        sim.set_mass(Vector([0, 0]), 0.5).unwrap();
+        assert_eq!(sim.origin_frequency(), 4.);
+        sim.pin(Vector([0, 0])).unwrap();
+        assert_eq!(sim.origin_frequency(), 0.);
+        assert_eq!(sim.origin_period(), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_max_stable_dt() {
+        let build = || {
+            Simulation::<f64, 8>::build()
+                .stiffness(3.)
+                .origin_stiffness(1.)
+                .boundary(BoundaryCondition::Periodic)
+                .random_init(0.5, 7)
+        };
+        let sim = build().finish();
+        // Periodic and of even side, so the bound is the checkerboard mode.
+        let dt = sim.max_stable_dt();
+        assert!((dt - 2. / (1f64 + 8. * 3.).sqrt()).abs() < 1e-12);
+
+        let mut stable = sim.clone();
+        for _ in 0..1000 {
+            stable.update_checked(0.5 * dt).unwrap();
+        }
+        assert!(stable.max_abs_diff(&build().finish()) < 10.);
+
+        let mut unstable = sim.clone();
+        assert!((0..1000).any(|_| unstable.update_checked(2. * dt).is_err()));
+
+        let heavy = build().mass(4.).finish();
+        assert!((heavy.max_stable_dt() - 2. * dt).abs() < 1e-12);
+
+        let still = Simulation::<f64, 3>::build()
+            .stiffness(0.)
+            .origin_stiffness(0.)
+            .finish();
+        assert_eq!(still.max_stable_dt(), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_adaptive_respects_floor() {
+        let mut sim = stiff();
+
+        let dt = sim.update_adaptive(0.1, 0.);
+
+        assert!(dt >= 1e-6);
+        assert!(dt / 2. < 1e-6);
+    }
+}
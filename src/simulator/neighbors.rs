@@ -0,0 +1,376 @@
+use crate::{
+    diagonal_stencil,
+    indexing::{deindex_in, filter_indices_in, offset_in, shape_len},
+    radius_stencil, stencil, Float, Vector,
+};
+
+use super::{BoundaryCondition, Simulation};
+
+/// Which particles the coupling bonds connect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Connectivity {
+    /// Nearest neighbors along each axis only.
+    #[default]
+    AxisNeighbors,
+    /// Also the diagonal neighbors within every plane spanned by two axes,
+    /// at offsets like `(±1, ±1, 0)`. These bonds resist shear.
+    WithDiagonals,
+    /// Every other particle of the surrounding `3.pow(DIMS)` block. The same
+    /// as `WithDiagonals` below three dimensions.
+    Moore,
+}
+
+impl Connectivity {
+    /// The `[up, down]` stencil pairs of the nearest-neighbor bonds, each
+    /// tagged with its kind: the stencil axis for axis bonds, or `DIMS` for
+    /// diagonal bonds.
+    fn near_stencil<const DIMS: usize>(self) -> Vec<(usize, [Vector<isize, DIMS>; 2])> {
+        let mut bonds: Vec<_> = stencil::<DIMS>().into_iter().enumerate().collect();
+        let max_axes = match self {
+            Connectivity::AxisNeighbors => return bonds,
+            Connectivity::WithDiagonals => 2,
+            Connectivity::Moore => DIMS,
+        };
+        bonds.extend(
+            diagonal_stencil::<DIMS>(max_axes)
+                .into_iter()
+                .map(|pair| (DIMS, pair)),
+        );
+        bonds
+    }
+
+    /// The stencil pairs within `radius` that are not nearest-neighbor bonds
+    /// of this connectivity.
+    pub(super) fn long_range_stencil<const DIMS: usize>(
+        self,
+        radius: usize,
+    ) -> Vec<[Vector<isize, DIMS>; 2]> {
+        let near = self.near_stencil::<DIMS>();
+        radius_stencil::<DIMS>(radius)
+            .into_iter()
+            .filter(|pair| near.iter().all(|(_, near)| near != pair))
+            .collect()
+    }
+
+    /// Every bond stencil pair, each tagged with its kind: the stencil axis
+    /// for axis bonds, `DIMS` for diagonal bonds, and `DIMS + 1 + i` for the
+    /// `i`-th pair of [`Connectivity::long_range_stencil`].
+    pub(super) fn bond_stencil<const DIMS: usize>(
+        self,
+        radius: usize,
+    ) -> Vec<(usize, [Vector<isize, DIMS>; 2])> {
+        let mut bonds = self.near_stencil::<DIMS>();
+        bonds.extend(
+            self.long_range_stencil::<DIMS>(radius)
+                .into_iter()
+                .enumerate()
+                .map(|(i, pair)| (DIMS + 1 + i, pair)),
+        );
+        bonds
+    }
+}
+
+/// The coupling neighbors of every particle of a grid under one boundary
+/// condition, connectivity and coupling radius, as flat offsets in stencil
+/// order, along with the kind of
+/// each bond (see [`Connectivity::bond_stencil`]). Connectivity rarely
+/// changes, so this is built once rather than resolved every step. The
+/// neighbors of particle `k` are `indices[starts[k]..starts[k + 1]]`.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Neighbors {
+    boundary: BoundaryCondition,
+    connectivity: Connectivity,
+    radius: usize,
+    starts: Box<[usize]>,
+    indices: Box<[usize]>,
+    kinds: Box<[usize]>,
+    /// The bond stencil as `[up, down]` pairs of offsets, flattened to
+    /// `DIMS` components each, see [`Neighbors::step`].
+    stencil: Box<[isize]>,
+    /// Which step of `stencil` each entry of `indices` is reached by.
+    steps: Box<[usize]>,
+    /// Every bond once, see [`Neighbors::unique_bonds`].
+    unique: Box<[(usize, usize, usize, usize)]>,
+}
+
+impl Neighbors {
+    pub(super) fn new<const DIMS: usize>(
+        shape: Vector<usize, DIMS>,
+        boundary: BoundaryCondition,
+        connectivity: Connectivity,
+        radius: usize,
+    ) -> Self {
+        let len = shape_len(shape).unwrap();
+        let bond_stencil = connectivity.bond_stencil::<DIMS>(radius);
+        let mut starts = Vec::with_capacity(len + 1);
+        let mut indices = Vec::with_capacity(len * 2 * bond_stencil.len());
+        let mut kinds = Vec::with_capacity(len * 2 * bond_stencil.len());
+        let mut steps = Vec::with_capacity(len * 2 * bond_stencil.len());
+        let mut unique = Vec::with_capacity(len * bond_stencil.len());
+
+        starts.push(0);
+        for k in 0..len {
+            let here = deindex_in(shape, k).unwrap().map(|i| i as isize);
+            for (s, &(kind, [stencil_up, stencil_down])) in bond_stencil.iter().enumerate() {
+                for (direction, step) in [stencil_up, stencil_down].into_iter().enumerate() {
+                    if let Some(n) = boundary.resolve(shape, here + step) {
+                        indices.push(offset_in(shape, n));
+                        kinds.push(kind);
+                        steps.push(2 * s + direction);
+                    }
+                }
+                let up = match boundary {
+                    BoundaryCondition::Reflective => filter_indices_in(shape, here + stencil_up),
+                    _ => boundary.resolve(shape, here + stencil_up),
+                };
+                if let Some(n) = up {
+                    unique.push((k, offset_in(shape, n), kind, 2 * s));
+                }
+            }
+            starts.push(indices.len());
+        }
+
+        Neighbors {
+            boundary,
+            connectivity,
+            radius,
+            starts: starts.into_boxed_slice(),
+            indices: indices.into_boxed_slice(),
+            kinds: kinds.into_boxed_slice(),
+            stencil: (bond_stencil.iter())
+                .flat_map(|(_, pair)| pair.iter().flat_map(|step| step.0))
+                .collect(),
+            steps: steps.into_boxed_slice(),
+            unique: unique.into_boxed_slice(),
+        }
+    }
+
+    /// The lattice offset of step `step` of the bond stencil: `2 * s` is
+    /// the upward offset of entry `s` of [`Connectivity::bond_stencil`] and
+    /// `2 * s + 1` the downward one. `DIMS` must be the one the lists were
+    /// built for.
+    #[inline]
+    pub(super) fn step<const DIMS: usize>(&self, step: usize) -> Vector<isize, DIMS> {
+        Vector::from_idx(|i| self.stencil[step * DIMS + i])
+    }
+
+    /// Like [`Neighbors::bonds`], but also yields the lattice offset from
+    /// `k` to each neighbor, before any wrapping or mirroring by the
+    /// boundary.
+    #[inline]
+    pub(super) fn bonds_with_offsets<const DIMS: usize>(
+        &self,
+        k: usize,
+    ) -> impl Iterator<Item = (usize, usize, Vector<isize, DIMS>)> + '_ {
+        let range = self.starts[k]..self.starts[k + 1];
+        self.bonds(k)
+            .zip(self.steps[range].iter())
+            .map(|((n, kind), &step)| (n, kind, self.step::<DIMS>(step)))
+    }
+
+    /// Every bond once, as the flat offsets `(k, n)` of its ends, its kind
+    /// and the [`Neighbors::step`] from `k` to `n`, `n` being `k`'s neighbor
+    /// in the positive direction of the stencil, in order of `k`. The
+    /// mirrored bonds of [`BoundaryCondition::Reflective`] are left out.
+    pub(super) fn unique_bonds(&self) -> &[(usize, usize, usize, usize)] {
+        &self.unique
+    }
+
+    /// Flat offsets of the neighbors of the particle at flat offset `k`,
+    /// each paired with the kind of the bond to it.
+    #[inline]
+    pub(super) fn bonds(&self, k: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let range = self.starts[k]..self.starts[k + 1];
+        self.indices[range.clone()]
+            .iter()
+            .copied()
+            .zip(self.kinds[range].iter().copied())
+    }
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    /// Rebuilds the neighbor lists if the boundary condition, connectivity or
+    /// coupling radius has changed since they were built.
+    pub(super) fn sync_neighbors(&mut self) {
+        let state = &self.state;
+        let Neighbors {
+            boundary,
+            connectivity,
+            radius,
+            ..
+        } = self.neighbors;
+        if (boundary, connectivity, radius)
+            != (state.boundary, state.connectivity, state.coupling_radius)
+        {
+            self.neighbors = Neighbors::new(
+                state.shape,
+                state.boundary,
+                state.connectivity,
+                state.coupling_radius,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Neighbors;
+    use crate::{
+        indexing::{deindex_in, filter_indices_in, offset_in, shape_len},
+        stencil, BoundaryCondition, Connectivity, Vector,
+    };
+
+    /// Checks the lists against the stencil filtered on the fly, and against
+    /// the lattice distance for boundaries that do not wrap.
+    fn assert_matches_on_the_fly<const DIMS: usize>(shape: Vector<usize, DIMS>) {
+        for boundary in [
+            BoundaryCondition::Free,
+            BoundaryCondition::Periodic,
+            BoundaryCondition::Fixed,
+            BoundaryCondition::Reflective,
+        ] {
+            let neighbors = Neighbors::new(shape, boundary, Connectivity::AxisNeighbors, 1);
+
+            for k in 0..shape_len(shape).unwrap() {
+                let here = deindex_in(shape, k).unwrap();
+                let mut expected = Vec::new();
+                for (axis, [stencil_up, stencil_down]) in stencil::<DIMS>().into_iter().enumerate()
+                {
+                    for step in [stencil_up, stencil_down] {
+                        let neighbor = here.map(|i| i as isize) + step;
+                        if let Some(n) = boundary.resolve(shape, neighbor) {
+                            expected.push((offset_in(shape, n), axis, step));
+                        }
+                    }
+                }
+                assert_eq!(
+                    neighbors.bonds_with_offsets::<DIMS>(k).collect::<Vec<_>>(),
+                    expected,
+                    "{boundary:?} particle {k}"
+                );
+
+                // Every bond up from `k` that is not a mirror image.
+                let up: Vec<_> = stencil::<DIMS>()
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(axis, [stencil_up, _])| {
+                        let neighbor = here.map(|i| i as isize) + stencil_up;
+                        let n = match boundary {
+                            BoundaryCondition::Reflective => filter_indices_in(shape, neighbor),
+                            _ => boundary.resolve(shape, neighbor),
+                        }?;
+                        Some((k, offset_in(shape, n), axis, 2 * axis))
+                    })
+                    .collect();
+                let unique: Vec<_> = (neighbors.unique_bonds().iter())
+                    .filter(|bond| bond.0 == k)
+                    .copied()
+                    .collect();
+                assert_eq!(unique, up, "{boundary:?} particle {k}");
+
+                if boundary != BoundaryCondition::Periodic {
+                    for (n, _) in neighbors.bonds(k) {
+                        let there = deindex_in(shape, n).unwrap();
+                        let distance = (0..DIMS).map(|i| here[i].abs_diff(there[i])).sum::<usize>();
+                        assert_eq!(distance, 1);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_neighbors_match_on_the_fly() {
+        assert_matches_on_the_fly(Vector([1; 2]));
+        assert_matches_on_the_fly(Vector([2; 2]));
+        assert_matches_on_the_fly(Vector([5; 2]));
+        assert_matches_on_the_fly(Vector([4; 3]));
+        assert_matches_on_the_fly(Vector([6; 1]));
+        assert_matches_on_the_fly(Vector([4, 3]));
+        assert_matches_on_the_fly(Vector([1, 5]));
+        assert_matches_on_the_fly(Vector([2, 3, 4]));
+    }
+
+    #[test]
+    fn test_neighbor_counts() {
+        let free = Neighbors::new(
+            Vector([4; 2]),
+            BoundaryCondition::Free,
+            Connectivity::AxisNeighbors,
+            1,
+        );
+        assert_eq!(free.bonds(0).count(), 2);
+        assert_eq!(free.bonds(1).count(), 3);
+        assert_eq!(free.bonds(5).count(), 4);
+        // Each of the 24 bonds of a free 4x4 lattice is seen from both ends.
+        assert_eq!(free.indices.len(), 48);
+
+        let periodic = Neighbors::new(
+            Vector([4; 2]),
+            BoundaryCondition::Periodic,
+            Connectivity::AxisNeighbors,
+            1,
+        );
+        assert!((0..16).all(|k| periodic.bonds(k).count() == 4));
+        assert_eq!(
+            periodic.bonds(0).collect::<Vec<_>>(),
+            [(4, 0), (12, 0), (1, 1), (3, 1)]
+        );
+    }
+
+    #[test]
+    fn test_diagonal_neighbor_counts() {
+        use BoundaryCondition::Periodic;
+
+        let diagonals = Neighbors::new(Vector([4; 2]), Periodic, Connectivity::WithDiagonals, 1);
+        assert!((0..16).all(|k| diagonals.bonds(k).count() == 8));
+        assert_eq!(diagonals.bonds(5).filter(|&(_, kind)| kind == 2).count(), 4);
+        let moore = Neighbors::new(Vector([4; 2]), Periodic, Connectivity::Moore, 1);
+        assert_eq!(
+            moore,
+            Neighbors {
+                connectivity: Connectivity::Moore,
+                ..diagonals
+            }
+        );
+
+        let diagonals = Neighbors::new(Vector([4; 3]), Periodic, Connectivity::WithDiagonals, 1);
+        assert!((0..64).all(|k| diagonals.bonds(k).count() == 18));
+        let moore = Neighbors::new(Vector([4; 3]), Periodic, Connectivity::Moore, 1);
+        assert!((0..64).all(|k| moore.bonds(k).count() == 26));
+
+        let free = Neighbors::new(
+            Vector([3; 2]),
+            BoundaryCondition::Free,
+            Connectivity::Moore,
+            1,
+        );
+        assert_eq!(free.bonds(0).count(), 3);
+        assert_eq!(free.bonds(4).count(), 8);
+    }
+
+    #[test]
+    fn test_rectangular_neighbors() {
+        // A 4x3 periodic lattice wraps each axis at its own extent.
+        let periodic = Neighbors::new(
+            Vector([4, 3]),
+            BoundaryCondition::Periodic,
+            Connectivity::AxisNeighbors,
+            1,
+        );
+        assert_eq!(
+            periodic.bonds(0).collect::<Vec<_>>(),
+            [(3, 0), (9, 0), (1, 1), (2, 1)]
+        );
+        // Each of the 9 + 8 bonds of a free 4x3 lattice is seen from both ends.
+        let free = Neighbors::new(
+            Vector([4, 3]),
+            BoundaryCondition::Free,
+            Connectivity::AxisNeighbors,
+            1,
+        );
+        assert_eq!(free.indices.len(), 34);
+        assert_eq!(free.unique_bonds().len(), 17);
+    }
+}
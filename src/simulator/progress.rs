@@ -0,0 +1,122 @@
+use crate::Float;
+
+use super::Simulation;
+
+/// Receives progress from [`Simulation::run_with_progress`]. Every method
+/// does nothing by default, and `()` is the sink that reports nothing.
+pub trait ProgressSink {
+    /// Called once before the first step with the number of steps to take.
+    fn start(&mut self, _steps: usize) {}
+
+    /// Called after every step.
+    fn tick(&mut self) {}
+
+    /// Called once after the last step.
+    fn finish(&mut self) {}
+}
+
+impl ProgressSink for () {}
+
+/// `None` reports nothing, e.g. to show a bar only on a terminal.
+impl<S: ProgressSink> ProgressSink for Option<S> {
+    fn start(&mut self, steps: usize) {
+        if let Some(sink) = self {
+            sink.start(steps);
+        }
+    }
+
+    fn tick(&mut self) {
+        if let Some(sink) = self {
+            sink.tick();
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(sink) = self {
+            sink.finish();
+        }
+    }
+}
+
+/// A [`tqdm`] progress bar on standard error, enabled by the `tqdm`
+/// feature. Drawing errors are ignored.
+#[cfg(feature = "tqdm")]
+#[derive(Default)]
+pub struct TqdmProgress {
+    bar: Option<tqdm::Pbar>,
+}
+
+#[cfg(feature = "tqdm")]
+impl ProgressSink for TqdmProgress {
+    fn start(&mut self, steps: usize) {
+        self.bar = Some(tqdm::pbar(Some(steps)));
+    }
+
+    fn tick(&mut self) {
+        if let Some(bar) = &mut self.bar {
+            let _ = bar.update(1);
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(mut bar) = self.bar.take() {
+            let _ = bar.close();
+        }
+    }
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    /// Like [`Simulation::run`], but reports to `progress` as it goes.
+    pub fn run_with_progress(&mut self, steps: usize, dt: T, progress: &mut impl ProgressSink) {
+        progress.start(steps);
+        for _ in 0..steps {
+            self.update(dt);
+            progress.tick();
+        }
+        progress.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProgressSink;
+    use crate::Simulation;
+
+    #[derive(Default)]
+    struct Counter {
+        total: Option<usize>,
+        ticks: usize,
+        finished: bool,
+    }
+
+    impl ProgressSink for Counter {
+        fn start(&mut self, steps: usize) {
+            self.total = Some(steps);
+        }
+
+        fn tick(&mut self) {
+            assert!(!self.finished);
+            self.ticks += 1;
+        }
+
+        fn finish(&mut self) {
+            self.finished = true;
+        }
+    }
+
+    #[test]
+    fn test_progress_ticks_every_step() {
+        let mut sim = Simulation::<f64, 3>::build().finish();
+        let mut counter = Counter::default();
+        sim.run_with_progress(25, 1e-2, &mut counter);
+        assert_eq!(counter.total, Some(25));
+        assert_eq!(counter.ticks, 25);
+        assert!(counter.finished);
+        assert_eq!(sim.steps(), 25);
+
+        let mut none: Option<Counter> = None;
+        sim.run_with_progress(5, 1e-2, &mut none);
+        sim.run_with_progress(5, 1e-2, &mut ());
+        assert_eq!(sim.steps(), 35);
+    }
+}
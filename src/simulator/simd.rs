@@ -0,0 +1,326 @@
+//! SIMD kernel for the acceleration computation, enabled by the `simd`
+//! feature.
+//!
+//! The lattice is processed one line at a time, a line being the particles
+//! that differ only in their last index. The components of a line's
+//! particles are contiguous, so the origin, damping and coupling forces
+//! become element-wise operations on `DIMS` floats per particle, done
+//! several lanes at a time with [`wide`]. Only the two ends of each line go
+//! through the boundary condition one particle at a time. Summation order
+//! differs from the scalar path, so results agree to rounding, not bitwise.
+
+use std::{
+    any::TypeId,
+    ops::{Add, Mul, Neg, Sub},
+};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use wide::{f32x8, f64x4};
+
+use crate::{
+    indexing::{deindex_in, offset_in},
+    stencil, BoundaryCondition, Connectivity, Float, Vector,
+};
+
+use super::{forcing::ForceField, SimulationState};
+
+/// A float type with a SIMD counterpart.
+trait Lanes: Float {
+    type Simd: Copy
+        + Add<Output = Self::Simd>
+        + Sub<Output = Self::Simd>
+        + Mul<Output = Self::Simd>
+        + Neg<Output = Self::Simd>;
+
+    const LANES: usize;
+
+    fn splat(x: Self) -> Self::Simd;
+
+    /// Loads up to `LANES` elements, padding with zero.
+    fn load(x: &[Self]) -> Self::Simd;
+
+    /// Stores the first `out.len()` lanes.
+    fn store(v: Self::Simd, out: &mut [Self]);
+}
+
+macro_rules! impl_lanes {
+    ($($t:ty => $simd:ty, $lanes:literal);+$(;)?) => {
+        $(impl Lanes for $t {
+            type Simd = $simd;
+
+            const LANES: usize = $lanes;
+
+            #[inline]
+            fn splat(x: Self) -> Self::Simd {
+                <$simd>::splat(x)
+            }
+
+            #[inline]
+            fn load(x: &[Self]) -> Self::Simd {
+                let mut lanes = [0.; $lanes];
+                lanes[..x.len()].copy_from_slice(x);
+                <$simd>::new(lanes)
+            }
+
+            #[inline]
+            fn store(v: Self::Simd, out: &mut [Self]) {
+                let n = out.len();
+                out.copy_from_slice(&v.to_array()[..n]);
+            }
+        })+
+    };
+}
+
+impl_lanes!(f32 => f32x8, 8; f64 => f64x4, 4);
+
+/// `out = f(out, a, b)`, element-wise.
+#[inline]
+fn zip3<L: Lanes>(
+    out: &mut [L],
+    a: &[L],
+    b: &[L],
+    f: impl Fn(L::Simd, L::Simd, L::Simd) -> L::Simd,
+) {
+    for ((out, a), b) in out
+        .chunks_mut(L::LANES)
+        .zip(a.chunks(L::LANES))
+        .zip(b.chunks(L::LANES))
+    {
+        L::store(f(L::load(out), L::load(a), L::load(b)), out);
+    }
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> SimulationState<T, SIZE, DIMS> {
+    /// The SIMD counterpart of [`Self::accelerations`]. Returns `false`,
+    /// leaving `acc` untouched, if `T` has no SIMD kernel or the lattice has
+    /// diagonal or long-range bonds, a rest length, a cubic term or a
+    /// stiffness field, which the kernel does not handle.
+    pub(super) fn simd_accelerations(
+        &self,
+        force_field: &ForceField<T, DIMS>,
+        time: T,
+        pos: &[Vector<T, DIMS>],
+        vel: &[Vector<T, DIMS>],
+        acc: &mut [Vector<T, DIMS>],
+    ) -> bool {
+        if self.connectivity != Connectivity::AxisNeighbors
+            || !self.long_range_stiffness.is_empty()
+            || !self.rest_length.is_zero()
+            || !self.cubic_coupling.is_zero()
+            || self.bond_stiffness.is_some()
+        {
+            return false;
+        }
+        if TypeId::of::<T>() == TypeId::of::<f32>() {
+            self.forces::<f32>(pos, vel, acc);
+        } else if TypeId::of::<T>() == TypeId::of::<f64>() {
+            self.forces::<f64>(pos, vel, acc);
+        } else {
+            return false;
+        }
+
+        // Only a force field or a clamping boundary needs the grid indices.
+        let per_particle = force_field.is_set() || self.boundary == BoundaryCondition::Fixed;
+        for (k, acc) in acc.iter_mut().enumerate() {
+            if self.pinned[k] {
+                *acc = Vector::zero();
+                continue;
+            }
+            if per_particle {
+                let here = deindex_in(self.shape, k).unwrap();
+                if self.boundary.is_clamped(self.shape, here) {
+                    *acc = Vector::zero();
+                    continue;
+                }
+                if let Some(external) = force_field.force(here, time) {
+                    *acc += external;
+                }
+            }
+            *acc = *acc / self.mass[k] + self.gravity;
+        }
+        true
+    }
+
+    /// Writes the origin, damping and coupling force on every particle into
+    /// `force`. `L` must be `T`.
+    fn forces<L: Lanes>(
+        &self,
+        pos: &[Vector<T, DIMS>],
+        vel: &[Vector<T, DIMS>],
+        force: &mut [Vector<T, DIMS>],
+    ) {
+        let pos: &[L] = bytemuck::cast_slice(pos);
+        let vel: &[L] = bytemuck::cast_slice(vel);
+        let force: &mut [L] = bytemuck::cast_slice_mut(force);
+        let line_len = self.shape[DIMS - 1] * DIMS;
+        if line_len == 0 {
+            return;
+        }
+
+        #[cfg(feature = "rayon")]
+        let lines = force.par_chunks_mut(line_len);
+        #[cfg(not(feature = "rayon"))]
+        let lines = force.chunks_mut(line_len);
+
+        lines
+            .enumerate()
+            .for_each(|(line, force)| self.line_forces(pos, vel, line, force));
+    }
+
+    /// The forces on the particles of one line.
+    fn line_forces<L: Lanes>(&self, pos: &[L], vel: &[L], line: usize, force: &mut [L]) {
+        let stiffness: Vector<L, DIMS> = self.stiffness.map(bytemuck::cast);
+        let origin_stiffness: L = bytemuck::cast(self.origin_stiffness);
+        let damping: L = bytemuck::cast(self.damping);
+        let (k0, c) = (L::splat(origin_stiffness), L::splat(damping));
+
+        let extent = self.shape[DIMS - 1];
+        let line_len = extent * DIMS;
+        let start = line * line_len;
+        let here = &pos[start..start + line_len];
+
+        zip3(force, here, &vel[start..start + line_len], |_, x, v| {
+            -(k0 * x + c * v)
+        });
+
+        let first = deindex_in(self.shape, line * extent)
+            .unwrap()
+            .map(|i| i as isize);
+        let stencil = stencil::<DIMS>();
+
+        // Along every axis but the last, the neighbors of a line form another
+        // whole line, which either exists or does not.
+        for (axis, [stencil_up, stencil_down]) in stencil[..DIMS - 1].iter().enumerate() {
+            let k = L::splat(stiffness[axis]);
+            for neighbor in [first + *stencil_up, first + *stencil_down] {
+                if let Some(n) = self.boundary.resolve(self.shape, neighbor) {
+                    let start = offset_in(self.shape, n) * DIMS;
+                    let there = &pos[start..start + line_len];
+                    zip3(force, there, here, |f, a, b| f + k * (a - b));
+                }
+            }
+        }
+
+        // Along the last axis, every particle but the end ones has both
+        // neighbors within the line.
+        let stiffness = stiffness[DIMS - 1];
+        let k = L::splat(stiffness);
+        let inner = line_len - DIMS;
+        zip3(
+            &mut force[..inner],
+            &here[DIMS..],
+            &here[..inner],
+            |f, a, b| f + k * (a - b),
+        );
+        zip3(
+            &mut force[DIMS..],
+            &here[..inner],
+            &here[DIMS..],
+            |f, a, b| f + k * (a - b),
+        );
+
+        let [stencil_up, stencil_down] = stencil[DIMS - 1];
+        let last = first.update(|axis, i| {
+            if axis == DIMS - 1 {
+                i + extent as isize - 1
+            } else {
+                i
+            }
+        });
+        for (end, neighbor) in [(extent - 1, last + stencil_up), (0, first + stencil_down)] {
+            if let Some(n) = self.boundary.resolve(self.shape, neighbor) {
+                let there = offset_in(self.shape, n) * DIMS;
+                for axis in 0..DIMS {
+                    let f = end * DIMS + axis;
+                    force[f] += stiffness * (pos[there + axis] - here[f]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BoundaryCondition, Float, Simulation, Vector};
+
+    /// Scrambles the lattice, then checks the SIMD accelerations against the
+    /// scalar ones particle by particle.
+    fn assert_matches_scalar<T: Float + std::fmt::Debug, const DIMS: usize>(
+        shape: Vector<usize, DIMS>,
+        boundary: BoundaryCondition,
+        tolerance: T,
+    ) {
+        let mut sim = Simulation::<T, 1, DIMS>::build()
+            .shape(shape)
+            .stiffness_axis(Vector::from_idx(|axis| T::from(1.5 + axis as f64).unwrap()))
+            .origin_stiffness(T::from(0.25).unwrap())
+            .damping(T::from(0.125).unwrap())
+            .boundary(boundary)
+            .finish();
+        for (indices, pos, vel) in sim.particles_mut() {
+            let seed = T::from(indices.map(|i| i as f64 + 1.).sum() * 0.7).unwrap();
+            *pos = Vector::from_idx(|axis| (seed * T::from(axis + 1).unwrap()).sin());
+            *vel = Vector::from_idx(|axis| (seed + T::from(axis).unwrap()).cos());
+        }
+        sim.set_mass(shape / Vector::broadcast(2), T::from(3).unwrap())
+            .unwrap();
+        sim.pin(Vector::zero()).unwrap();
+        sim.set_force_field(|index, time| index.map(|i| T::from(i).unwrap() * time));
+        sim.time = T::from(0.5).unwrap();
+
+        let state = &sim.state;
+        let mut acc = state.acc.clone();
+        assert!(state.simd_accelerations(
+            &sim.force_field,
+            sim.time,
+            &state.pos,
+            &state.vel,
+            &mut acc
+        ));
+
+        for (k, &acc) in acc.iter().enumerate() {
+            let scalar = state.acceleration(
+                &sim.force_field,
+                &sim.neighbors,
+                sim.time,
+                &state.pos,
+                &state.vel,
+                k,
+            );
+            for axis in 0..DIMS {
+                assert!(
+                    (acc[axis] - scalar[axis]).abs() <= tolerance,
+                    "{boundary:?} particle {k}: {acc:?} vs {scalar:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        for boundary in [
+            BoundaryCondition::Free,
+            BoundaryCondition::Periodic,
+            BoundaryCondition::Fixed,
+            BoundaryCondition::Reflective,
+        ] {
+            assert_matches_scalar::<f32, 2>(Vector([16; 2]), boundary, 1e-5);
+            assert_matches_scalar::<f32, 3>(Vector([5; 3]), boundary, 1e-5);
+            assert_matches_scalar::<f64, 2>(Vector([7; 2]), boundary, 1e-12);
+            assert_matches_scalar::<f64, 2>(Vector([1; 2]), boundary, 1e-12);
+            assert_matches_scalar::<f64, 1>(Vector([4; 1]), boundary, 1e-12);
+            assert_matches_scalar::<f32, 2>(Vector([12, 5]), boundary, 1e-5);
+            assert_matches_scalar::<f64, 3>(Vector([3, 6, 2]), boundary, 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_simd_empty_lattice() {
+        for shape in [Vector([0, 0]), Vector([4, 0]), Vector([0, 4])] {
+            let mut sim = Simulation::<f32, 1>::build().shape(shape).finish();
+            sim.update(0.1);
+            assert!(sim.state.acc.is_empty());
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Frequency spectra of probe trajectories, enabled by the `rustfft`
+//! feature.
+
+use rustfft::{num_complex::Complex, FftPlanner};
+
+use crate::{error::OutOfBounds, indexing::checked_offset_in, Float, Vector};
+
+use super::Simulation;
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    /// Runs `steps` steps of size `dt`, recording the position of the
+    /// particle at `index` after each, and returns the spectrum of that
+    /// trajectory as `(frequency, amplitude)` pairs from zero up to the
+    /// Nyquist frequency `1 / (2 * dt)`, in cycles per unit time.
+    ///
+    /// The mean is removed and a Hann window applied before the transform to
+    /// limit leakage between bins, and amplitudes are normalized so that a
+    /// sinusoid of amplitude `a` at a bin frequency shows as `a`. The
+    /// amplitude combines the components, `sqrt(Σ |X_d|²)`. Fails, without
+    /// stepping, if `index` lies outside the grid.
+    pub fn probe_spectrum(
+        &mut self,
+        index: Vector<usize, DIMS>,
+        steps: usize,
+        dt: T,
+    ) -> Result<Vec<(T, T)>, OutOfBounds> {
+        let k = checked_offset_in(self.state.shape, index).ok_or(OutOfBounds)?;
+        let mut series = Vec::with_capacity(steps);
+        for _ in 0..steps {
+            self.update(dt);
+            series.push(self.state.pos[k].map(|x| x.to_f64().unwrap()));
+        }
+
+        let n = series.len();
+        let window: Vec<f64> = (0..n)
+            .map(|t| {
+                let phase = std::f64::consts::TAU * t as f64 / n as f64;
+                0.5 * (1. - phase.cos())
+            })
+            .collect();
+        let gain: f64 = window.iter().sum();
+        let bins = n / 2 + 1;
+
+        let fft = FftPlanner::new().plan_fft_forward(n);
+        let mut power = vec![0.; bins];
+        for axis in 0..DIMS {
+            let mean = series.iter().map(|x| x[axis]).sum::<f64>() / n as f64;
+            let mut buffer: Vec<_> = series
+                .iter()
+                .zip(&window)
+                .map(|(x, w)| Complex::new((x[axis] - mean) * w, 0.))
+                .collect();
+            fft.process(&mut buffer);
+            for (power, x) in power.iter_mut().zip(&buffer) {
+                *power += x.norm_sqr();
+            }
+        }
+
+        let dt = dt.to_f64().unwrap();
+        Ok(power
+            .into_iter()
+            .enumerate()
+            .map(|(bin, power)| {
+                // Every bin but DC and Nyquist has a mirror image of equal
+                // power at the negative frequency.
+                let sides = if bin == 0 || 2 * bin == n { 1. } else { 2. };
+                let frequency = bin as f64 / (n as f64 * dt);
+                let amplitude = sides * power.sqrt() / gain;
+                (T::from(frequency).unwrap(), T::from(amplitude).unwrap())
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::OutOfBounds, Simulation, Vector};
+
+    #[test]
+    fn test_uncoupled_oscillator_peak() {
+        let origin_stiffness = 4.;
+        let mut sim = Simulation::<f64, 3>::build()
+            .stiffness(0.)
+            .origin_stiffness(origin_stiffness)
+            .finish();
+        sim.set_position(Vector([1, 1]), Vector([0.5, 0.])).unwrap();
+
+        let dt = 1e-2;
+        let spectrum = sim.probe_spectrum(Vector([1, 1]), 4096, dt).unwrap();
+        assert_eq!(spectrum.len(), 2049);
+        assert!((spectrum[1].0 - 1. / (4096. * dt)).abs() < 1e-12);
+
+        let &(peak, amplitude) = spectrum.iter().max_by(|a, b| a.1.total_cmp(&b.1)).unwrap();
+        let expected = origin_stiffness.sqrt() / std::f64::consts::TAU;
+        let resolution = 1. / (4096. * dt);
+        assert!(
+            (peak - expected).abs() <= resolution,
+            "{peak} vs {expected}"
+        );
+        // Within the Hann window's scalloping loss of the true amplitude.
+        assert!(amplitude > 0.5 * 0.84 && amplitude <= 0.5, "{amplitude}");
+
+        // Sharp: far from the peak, the window has suppressed the leakage.
+        for &(frequency, a) in &spectrum {
+            if (frequency - expected).abs() > 10. * resolution {
+                assert!(a < 1e-2 * amplitude, "{frequency}: {a}");
+            }
+        }
+
+        assert_eq!(sim.probe_spectrum(Vector([3, 0]), 16, dt), Err(OutOfBounds));
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{
+    error::OutOfBounds,
+    indexing::{deindex, deindex_in, len, offset, offset_in},
+    BoundaryCondition, Float, Vector,
+};
+
+use super::{forcing::ForceField, zeroed_buffer, Neighbors, Simulation, SimulationState};
+
+/// The elements of the per-particle `buffer` of a grid of the given `shape`
+/// that belong to the region of side `SUB` starting at `lo`, in the region's
+/// row-major order.
+fn region<X: Copy, const SUB: usize, const DIMS: usize>(
+    buffer: &[X],
+    shape: Vector<usize, DIMS>,
+    lo: Vector<usize, DIMS>,
+) -> Box<[X]> {
+    (0..len::<SUB, DIMS>())
+        .map(|k| buffer[offset_in(shape, lo + deindex::<SUB, DIMS>(k).unwrap())])
+        .collect()
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    /// A new simulation of the `SUB.pow(DIMS)` particles whose grid indices
+    /// lie between `lo` and `lo + SUB - 1` along every axis, to continue
+    /// that region in isolation. Fails if the region does not fit in the
+    /// grid.
+    ///
+    /// Positions, velocities, masses, pins, per-bond stiffness, the clock
+    /// and every physical and stepping parameter are copied, as is the
+    /// force field, which keeps seeing the particles' indices on this grid.
+    /// The boundary becomes [`BoundaryCondition::Fixed`], so the cut holds
+    /// still and anchors the interior, which then moves like it would here
+    /// until a disturbance from outside would have reached it. The step
+    /// counter starts at zero, [`Simulation::reset`] returns to the
+    /// region's initial positions, and the thermostat, probes and driven
+    /// edge are not carried over.
+    pub fn subdomain<const SUB: usize>(
+        &self,
+        lo: Vector<usize, DIMS>,
+    ) -> Result<Simulation<T, SUB, DIMS>, OutOfBounds> {
+        if !lo
+            .map2(self.state.shape, |i, extent| {
+                i.checked_add(SUB).is_some_and(|end| end <= extent)
+            })
+            .all()
+        {
+            return Err(OutOfBounds);
+        }
+        let state = &self.state;
+        let boundary = BoundaryCondition::Fixed;
+        let mut simulation = Simulation {
+            state: SimulationState {
+                shape: Vector::broadcast(SUB),
+                stiffness: state.stiffness,
+                rest_length: state.rest_length,
+                cubic_coupling: state.cubic_coupling,
+                connectivity: state.connectivity,
+                diagonal_stiffness: state.diagonal_stiffness,
+                coupling_radius: state.coupling_radius,
+                long_range_stiffness: state.long_range_stiffness.clone(),
+                bond_stiffness: (state.bond_stiffness.as_deref())
+                    .map(|field| region::<_, SUB, DIMS>(field, state.shape, lo)),
+                origin_stiffness: state.origin_stiffness,
+                mass: region::<_, SUB, DIMS>(&state.mass, state.shape, lo),
+                damping: state.damping,
+                gravity: state.gravity,
+                boundary,
+                pos: region::<_, SUB, DIMS>(&state.pos, state.shape, lo),
+                vel: region::<_, SUB, DIMS>(&state.vel, state.shape, lo),
+                acc: region::<_, SUB, DIMS>(&state.acc, state.shape, lo),
+                pinned: region::<_, SUB, DIMS>(&state.pinned, state.shape, lo),
+            },
+            force_field: self.force_field.shifted(lo),
+            neighbors: Neighbors::new::<DIMS>(
+                Vector::broadcast(SUB),
+                boundary,
+                state.connectivity,
+                state.coupling_radius,
+            ),
+            threads: self.threads,
+            time: self.time,
+            steps: 0,
+            integrator: self.integrator,
+            min_dt: self.min_dt,
+            last_adaptive_dt: self.last_adaptive_dt,
+            acc_stale: false,
+            tmp_acc: zeroed_buffer(len::<SUB, DIMS>()),
+            tmp_pos: zeroed_buffer(len::<SUB, DIMS>()),
+            tmp_vel: zeroed_buffer(len::<SUB, DIMS>()),
+            rk4_sums: Vec::new(),
+            initial_pos: region::<_, SUB, DIMS>(&self.initial_pos, state.shape, lo),
+            langevin: None,
+            history: None,
+            driven: None,
+        };
+        simulation.enforce_boundary();
+        simulation.refresh_acc();
+        Ok(simulation)
+    }
+
+    /// A coarser simulation of side `COARSE`, each of whose particles
+    /// stands for a block of `extent / COARSE` particles along every axis.
+    /// A `COARSE` of zero is a compile-time error, and one that does not
+    /// divide the extent of every axis panics.
+    ///
+    /// A coarse particle's mass is the total of its block and its position
+    /// and velocity are the mass-weighted means, so mass and momentum are
+    /// conserved, except where a block is brought to rest: if any of its
+    /// particles is pinned, which pins the block, or if a fixed boundary
+    /// clamps it. The initial positions restored by [`Simulation::reset`]
+    /// are averaged the same way, and per-bond stiffness is averaged over
+    /// the block. Every other parameter and the clock are copied unchanged,
+    /// in particular the spring constants, which are not rescaled for the
+    /// coarser spacing. The step counter starts at zero, and the force
+    /// field, thermostat, probes and driven edge are not carried over.
+    pub fn downsample<const COARSE: usize>(&self) -> Simulation<T, COARSE, DIMS> {
+        const { assert!(COARSE > 0, "the coarse side must be positive") };
+        let state = &self.state;
+        assert!(
+            state
+                .shape
+                .map(|extent| extent.is_multiple_of(COARSE))
+                .all(),
+            "the coarse side must divide every extent"
+        );
+        let factor = state.shape / Vector::broadcast(COARSE);
+        let block = |k| offset::<COARSE, DIMS>(deindex_in(state.shape, k).unwrap() / factor);
+
+        let mut mass = vec![T::zero(); len::<COARSE, DIMS>()].into_boxed_slice();
+        let mut pos = zeroed_buffer(len::<COARSE, DIMS>());
+        let mut vel = zeroed_buffer(len::<COARSE, DIMS>());
+        let mut initial_pos = zeroed_buffer(len::<COARSE, DIMS>());
+        let mut pinned = vec![false; len::<COARSE, DIMS>()].into_boxed_slice();
+        let mut bond_stiffness = state
+            .bond_stiffness
+            .as_ref()
+            .map(|_| vec![T::zero(); len::<COARSE, DIMS>()].into_boxed_slice());
+        for (k, &m) in state.mass.iter().enumerate() {
+            let c = block(k);
+            mass[c] += m;
+            pos[c] += state.pos[k] * m;
+            vel[c] += state.vel[k] * m;
+            initial_pos[c] += self.initial_pos[k] * m;
+            pinned[c] |= state.pinned[k];
+            if let (Some(coarse), Some(fine)) = (&mut bond_stiffness, &state.bond_stiffness) {
+                coarse[c] += fine[k];
+            }
+        }
+        for (c, &m) in mass.iter().enumerate() {
+            pos[c] = pos[c] / m;
+            vel[c] = vel[c] / m;
+            initial_pos[c] = initial_pos[c] / m;
+        }
+        if let Some(coarse) = &mut bond_stiffness {
+            let count = T::from(state.pos.len() / len::<COARSE, DIMS>()).unwrap();
+            coarse.iter_mut().for_each(|s| *s = *s / count);
+        }
+
+        let mut simulation = Simulation {
+            state: SimulationState {
+                shape: Vector::broadcast(COARSE),
+                stiffness: state.stiffness,
+                rest_length: state.rest_length,
+                cubic_coupling: state.cubic_coupling,
+                connectivity: state.connectivity,
+                diagonal_stiffness: state.diagonal_stiffness,
+                coupling_radius: state.coupling_radius,
+                long_range_stiffness: state.long_range_stiffness.clone(),
+                bond_stiffness,
+                origin_stiffness: state.origin_stiffness,
+                mass,
+                damping: state.damping,
+                gravity: state.gravity,
+                boundary: state.boundary,
+                pos,
+                vel,
+                acc: zeroed_buffer(len::<COARSE, DIMS>()),
+                pinned,
+            },
+            force_field: ForceField::none(),
+            neighbors: Neighbors::new::<DIMS>(
+                Vector::broadcast(COARSE),
+                state.boundary,
+                state.connectivity,
+                state.coupling_radius,
+            ),
+            threads: self.threads,
+            time: self.time,
+            steps: 0,
+            integrator: self.integrator,
+            min_dt: self.min_dt,
+            last_adaptive_dt: self.last_adaptive_dt,
+            acc_stale: false,
+            tmp_acc: zeroed_buffer(len::<COARSE, DIMS>()),
+            tmp_pos: zeroed_buffer(len::<COARSE, DIMS>()),
+            tmp_vel: zeroed_buffer(len::<COARSE, DIMS>()),
+            rk4_sums: Vec::new(),
+            initial_pos,
+            langevin: None,
+            history: None,
+            driven: None,
+        };
+        simulation.enforce_boundary();
+        simulation.refresh_acc();
+        simulation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::OutOfBounds, BoundaryCondition, Simulation, Vector};
+
+    #[test]
+    fn test_subdomain_copies_region() {
+        let mut sim = Simulation::<f64, 8>::build()
+            .stiffness(2.)
+            .random_init(0.5, 4)
+            .boundary(BoundaryCondition::Periodic)
+            .finish();
+        sim.set_velocity(Vector([3, 4]), Vector([1., -1.])).unwrap();
+        sim.set_mass(Vector([4, 3]), 3.).unwrap();
+        sim.run(20, 1e-2);
+
+        let sub = sim.subdomain::<4>(Vector([2, 3])).unwrap();
+        assert_eq!(sub.time, sim.time);
+        assert_eq!(sub.state.boundary, BoundaryCondition::Fixed);
+        assert_eq!(sub.state.mass[4 * 2], 3.);
+        for i in 0..4 {
+            for j in 0..4 {
+                let there = Vector([i + 2, j + 3]);
+                let here = Vector([i, j]);
+                assert_eq!(sub.position(here), sim.position(there));
+                let edge = i == 0 || j == 0 || i == 3 || j == 3;
+                if edge {
+                    assert_eq!(sub.velocity(here), Some(Vector::zero()));
+                } else {
+                    assert_eq!(sub.velocity(here), sim.velocity(there));
+                }
+            }
+        }
+
+        assert_eq!(
+            sim.subdomain::<8>(Vector([0, 0])).unwrap().state.pos,
+            sim.state.pos
+        );
+        assert_eq!(sim.subdomain::<4>(Vector([5, 0])).err(), Some(OutOfBounds));
+        assert_eq!(
+            sim.subdomain::<4>(Vector([0, usize::MAX])).err(),
+            Some(OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn test_downsample_averages_blocks() {
+        let mut sim = Simulation::<f64, 8>::build().finish();
+        for (_, pos, vel) in sim.particles_mut() {
+            *pos = Vector([0.5, -0.25]);
+            *vel = Vector([1., 2.]);
+        }
+        let coarse = sim.downsample::<4>();
+        assert!(coarse
+            .particles()
+            .all(|(_, pos, vel)| pos == Vector([0.5, -0.25]) && vel == Vector([1., 2.])));
+        assert!(coarse.state.mass.iter().all(|&m| m == 4.));
+
+        for (index, pos, _) in sim.particles_mut() {
+            *pos = index.map(|i| i as f64) * Vector([1., 10.]);
+        }
+        sim.set_mass(Vector([1, 1]), 3.).unwrap();
+        sim.set_velocity(Vector([5, 6]), Vector([-3., 0.5]))
+            .unwrap();
+        assert_eq!(sim.downsample::<2>().total_momentum(), sim.total_momentum());
+
+        sim.pin(Vector([7, 0])).unwrap();
+        let coarse = sim.downsample::<4>();
+        // Block (1, 2) covers rows 2..4 and columns 4..6.
+        assert_eq!(coarse.position(Vector([1, 2])), Some(Vector([2.5, 45.])));
+        // Block (0, 0) weighs (1, 1) three times as much as the others.
+        assert_eq!(
+            coarse.position(Vector([0, 0])),
+            Some(Vector([4. / 6., 40. / 6.]))
+        );
+        assert_eq!(coarse.state.mass[0], 6.);
+        assert!(coarse.state.pinned[12]);
+        assert_eq!(coarse.state.pinned.iter().filter(|&&p| p).count(), 1);
+        assert_eq!(coarse.velocity(Vector([3, 0])), Some(Vector::zero()));
+
+        let single = sim.downsample::<1>();
+        assert_eq!(single.state.mass[..], [66.]);
+        assert_eq!(sim.downsample::<8>().state.pos, sim.state.pos);
+    }
+
+    #[test]
+    fn test_subdomain_interior_follows_source() {
+        // A disturbance at the center stays clear of the cut for a while,
+        // so the two interiors should move together until then.
+        let mut sim = Simulation::<f64, 16>::build().stiffness(1.).finish();
+        sim.set_velocity(Vector([8, 8]), Vector([0., 1.])).unwrap();
+        sim.set_force_field(|index, _| Vector([0., (index[0] == 8) as u8 as f64 * 1e-3]));
+        let mut sub = sim.subdomain::<8>(Vector([4, 4])).unwrap();
+
+        sim.run(50, 1e-2);
+        sub.run(50, 1e-2);
+        for i in 2..6 {
+            for j in 2..6 {
+                let there = sim.position(Vector([i + 4, j + 4])).unwrap();
+                let here = sub.position(Vector([i, j])).unwrap();
+                assert!((there - here).norm() < 1e-6, "{there:?} vs {here:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{Float, Vector};
+
+use super::Simulation;
+
+/// A Langevin heat bath: friction against the lattice frame plus the random
+/// kicks that balance it at `temperature`.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Langevin<T> {
+    temperature: T,
+    friction: T,
+    rng: StdRng,
+}
+
+/// A standard normal sample, by the Box–Muller transform.
+pub(super) fn gaussian<T: Float>(rng: &mut impl Rng) -> T {
+    let radius = (-2. * (1. - rng.random::<f64>()).ln()).sqrt();
+    let angle = std::f64::consts::TAU * rng.random::<f64>();
+    T::from(radius * angle.cos()).unwrap()
+}
+
+impl<T: Float, const SIZE: usize, const DIMS: usize> Simulation<T, SIZE, DIMS> {
+    /// Couples every particle to a heat bath at `temperature`, in units with
+    /// Boltzmann's constant equal to one.
+    ///
+    /// After every [`Simulation::update`] each free particle feels the
+    /// friction force `-friction * mass * vel` together with a Gaussian
+    /// random force whose variance follows from the fluctuation–dissipation
+    /// relation. The two are integrated exactly over the step, so a velocity
+    /// component becomes `c * v + sqrt((1 - c^2) * temperature / mass) * xi`
+    /// with `c = exp(-friction * dt)` and `xi` standard normal. The noise
+    /// is drawn from an RNG seeded with `seed`, so runs are reproducible.
+    ///
+    /// Checkpoints do not record the thermostat.
+    pub fn enable_langevin(&mut self, temperature: T, friction: T, seed: u64) {
+        self.langevin = Some(Langevin {
+            temperature,
+            friction,
+            rng: StdRng::seed_from_u64(seed),
+        });
+    }
+
+    /// Removes the heat bath added by [`Simulation::enable_langevin`].
+    pub fn disable_langevin(&mut self) {
+        self.langevin = None;
+    }
+
+    /// Applies the heat bath, if any, to the velocities for a step of `dt`.
+    pub(super) fn thermalize(&mut self, dt: T) {
+        let Some(langevin) = &mut self.langevin else {
+            return;
+        };
+        let state = &mut self.state;
+        let decay = (-langevin.friction * dt).exp();
+        let spread = (T::one() - decay * decay) * langevin.temperature;
+        for (k, vel) in state.vel.iter_mut().enumerate() {
+            if state.pinned[k] {
+                continue;
+            }
+            let sigma = (spread / state.mass[k]).sqrt();
+            *vel = Vector(std::array::from_fn(|axis| {
+                vel[axis] * decay + sigma * gaussian::<T>(&mut langevin.rng)
+            }));
+        }
+        // A damping force depends on the velocities just changed.
+        if !state.damping.is_zero() {
+            self.acc_stale = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BoundaryCondition, Simulation, Vector};
+
+    #[test]
+    fn test_langevin_equipartition() {
+        let mut sim = Simulation::<f64, 4>::build()
+            .stiffness(1.)
+            .origin_stiffness(1.)
+            .mass(2.)
+            .boundary(BoundaryCondition::Periodic)
+            .finish();
+        let temperature = 0.5;
+        sim.enable_langevin(temperature, 1., 11);
+        sim.run(200, 0.05);
+
+        let steps = 10_000;
+        let mut kinetic = 0.;
+        for _ in 0..steps {
+            sim.update(0.05);
+            kinetic += sim.kinetic_energy();
+        }
+        kinetic /= steps as f64;
+
+        // Half the temperature for each of the 16 * 2 velocity components.
+        let target = 16. * 2. * temperature / 2.;
+        assert!(
+            (kinetic - target).abs() < 0.05 * target,
+            "{kinetic} vs {target}"
+        );
+    }
+
+    #[test]
+    fn test_langevin_is_seeded() {
+        let run = |seed| {
+            let mut sim = Simulation::<f64, 3>::build().finish();
+            sim.enable_langevin(1., 0.5, seed);
+            sim.run(50, 0.01);
+            sim
+        };
+        assert_eq!(run(5).state.vel, run(5).state.vel);
+        assert_ne!(run(5).state.vel, run(6).state.vel);
+
+        let mut sim = Simulation::<f64, 3>::build().finish();
+        sim.enable_langevin(1., 0.5, 5);
+        sim.disable_langevin();
+        sim.run(50, 0.01);
+        assert!(sim.state.vel.iter().all(|&v| v == Vector::zero()));
+    }
+}
@@ -2,6 +2,8 @@ use bytemuck::{Pod, Zeroable};
 
 use std::ops::{Index, IndexMut};
 
+use crate::error::LengthMismatch;
+
 /// This attempts to compile invalid types
 /// ```compile_fail
 /// use rtdriver::Vector;
@@ -16,9 +18,62 @@ use std::ops::{Index, IndexMut};
 #[repr(transparent)]
 pub struct Vector<T, const DIMS: usize = { crate::DIMS }>(pub [T; DIMS]);
 
+/// A two-dimensional [`Vector`], the default for `DIMS`.
+pub type Vec2<T> = Vector<T, 2>;
+
+/// A three-dimensional [`Vector`]. Every API that takes or returns a
+/// [`Vector`] is generic over `DIMS`, so this works wherever [`Vec2`] does:
+/// ```
+/// use rtdriver::{Simulation, Vec3, Vector};
+///
+/// let a: Vec3<f64> = Vector([1., 2., 3.]);
+/// let b = Vec3::broadcast(0.5);
+/// assert_eq!(a.cross(b).dot(a), 0.);
+/// assert_eq!((a + b * 2.).z(), 4.);
+///
+/// let mut sim = Simulation::<f64, 4, 3>::build().stiffness(1.).finish();
+/// sim.set_force_field(|_, _| -> Vec3<f64> { Vector([0., 0., -0.1]) });
+/// sim.apply_impulse(Vector([1, 2, 3]), a).unwrap();
+/// let before = sim.total_energy();
+/// sim.run(10, 1e-2);
+/// assert_ne!(sim.total_energy(), before);
+/// ```
+pub type Vec3<T> = Vector<T, 3>;
+
 unsafe impl<T, const DIMS: usize> Pod for Vector<T, DIMS> where [T; DIMS]: Pod {}
 unsafe impl<T, const DIMS: usize> Zeroable for Vector<T, DIMS> where [T; DIMS]: Zeroable {}
 
+/// A running sum that carries the low-order bits lost to rounding along in
+/// a separate term and feeds them back into the next addition, by Kahan's
+/// algorithm, so its error does not grow with the number of terms.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Compensated<T> {
+    sum: T,
+    compensation: T,
+}
+
+impl<T: num::Float> Compensated<T> {
+    pub(crate) fn new() -> Self {
+        Compensated {
+            sum: T::zero(),
+            compensation: T::zero(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn add(&mut self, x: T) {
+        let x = x - self.compensation;
+        let sum = self.sum + x;
+        // Whatever of `x` did not make it into `sum`, negated.
+        self.compensation = (sum - self.sum) - x;
+        self.sum = sum;
+    }
+
+    pub(crate) fn total(self) -> T {
+        self.sum
+    }
+}
+
 impl<T, const DIMS: usize> Clone for Vector<T, DIMS>
 where
     [T; DIMS]: Clone,
@@ -59,6 +114,16 @@ impl<T, const DIMS: usize> Vector<T, DIMS> {
         Vector(self.0.map(map_fn))
     }
 
+    /// Combines `self` and `other` component by component.
+    #[inline]
+    pub fn map2<U, V>(self, other: Vector<U, DIMS>, map_fn: impl Fn(T, U) -> V) -> Vector<V, DIMS> {
+        let mut pairs = self.0.into_iter().zip(other.0);
+        Vector(std::array::from_fn(|_| {
+            let (a, b) = pairs.next().unwrap();
+            map_fn(a, b)
+        }))
+    }
+
     #[inline]
     pub fn update(self, update_fn: impl Fn(usize, T) -> T) -> Self
     where
@@ -72,20 +137,167 @@ impl<T, const DIMS: usize> Vector<T, DIMS> {
         Self(std::array::from_fn(idx_fn))
     }
 
+    /// Feeds the components, first to last, through `fold_fn` into an
+    /// accumulator starting at `init`.
+    #[inline]
+    pub fn fold<B>(self, init: B, fold_fn: impl Fn(B, T) -> B) -> B {
+        self.0.into_iter().fold(init, fold_fn)
+    }
+
+    /// Sum of the components, zero if there are none.
     #[inline]
     pub fn sum(self) -> T
     where
         T: std::ops::Add<Output = T> + num::Zero,
     {
-        self.0.into_iter().reduce(|a, b| a + b).unwrap_or(T::zero())
+        self.fold(T::zero(), |a, b| a + b)
     }
 
+    /// Like [`Vector::sum`], but with compensated summation, which keeps the
+    /// rounding error of the result independent of the number of
+    /// components.
+    #[inline]
+    pub fn sum_kahan(self) -> T
+    where
+        T: num::Float,
+    {
+        self.fold(Compensated::new(), |mut sum, x| {
+            sum.add(x);
+            sum
+        })
+        .total()
+    }
+
+    /// Product of the components, one if there are none.
+    #[inline]
+    pub fn product(self) -> T
+    where
+        T: std::ops::Mul<Output = T> + num::One,
+    {
+        self.fold(T::one(), |a, b| a * b)
+    }
+
+    /// Despite the name, the sum of the components. Kept as it was for
+    /// existing callers.
+    #[deprecated(note = "use sum or product")]
     #[inline]
     pub fn prod(self) -> T
     where
         T: std::ops::Add<Output = T> + num::Zero,
     {
-        self.0.into_iter().reduce(|a, b| a + b).unwrap_or(T::zero())
+        self.sum()
+    }
+}
+
+impl<
+        T: Copy + std::ops::Mul<Output = T> + std::ops::Add<Output = T> + num::Zero,
+        const DIMS: usize,
+    > Vector<T, DIMS>
+{
+    /// Component-wise product, summed.
+    #[inline]
+    pub fn dot(self, other: Vector<T, DIMS>) -> T {
+        self.map2(other, |a, b| a * b)
+            .iter()
+            .fold(T::zero(), |acc, x| acc + x)
+    }
+
+    #[inline]
+    pub fn norm_squared(self) -> T {
+        self.dot(self)
+    }
+}
+
+impl<T: num::Float, const DIMS: usize> Vector<T, DIMS> {
+    /// Euclidean length.
+    #[inline]
+    pub fn norm(self) -> T {
+        self.norm_squared().sqrt()
+    }
+
+    /// The unit vector in the direction of `self`, or `None` if its norm is
+    /// not greater than `T::epsilon()` (or is NaN).
+    pub fn normalized(self) -> Option<Self> {
+        let norm = self.norm();
+        (norm > T::epsilon()).then(|| self / norm)
+    }
+
+    /// Like [`Vector::normalized`], but returns the zero vector when there
+    /// is no direction.
+    pub fn normalize_or_zero(self) -> Self {
+        self.normalized().unwrap_or(Vector::zero())
+    }
+}
+
+impl<T: Copy + PartialOrd, const DIMS: usize> Vector<T, DIMS> {
+    /// Component-wise minimum. Where the components are unordered (a NaN is
+    /// involved), the component of `self` is kept.
+    #[inline]
+    pub fn min(self, other: Vector<T, DIMS>) -> Self {
+        self.map2(other, |a, b| if b < a { b } else { a })
+    }
+
+    /// Component-wise maximum. Where the components are unordered (a NaN is
+    /// involved), the component of `self` is kept.
+    #[inline]
+    pub fn max(self, other: Vector<T, DIMS>) -> Self {
+        self.map2(other, |a, b| if b > a { b } else { a })
+    }
+
+    /// Clamps each component into `lo[i]..=hi[i]`. Unlike [`Ord::clamp`]
+    /// this does not panic when `lo[i] > hi[i]`; that component becomes
+    /// `hi[i]`.
+    #[inline]
+    pub fn clamp(self, lo: Vector<T, DIMS>, hi: Vector<T, DIMS>) -> Self {
+        self.max(lo).min(hi)
+    }
+}
+
+impl<T: Copy + std::ops::Mul<Output = T> + std::ops::Sub<Output = T>> Vector<T, 3> {
+    #[inline]
+    pub fn cross(self, other: Vector<T, 3>) -> Vector<T, 3> {
+        let [a, b] = [self, other];
+        Vector([
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ])
+    }
+}
+
+impl<T: Copy, const DIMS: usize> Vector<T, DIMS> {
+    /// The first component. Using it on a zero-dimensional vector is a
+    /// compile-time error.
+    #[inline]
+    pub fn x(&self) -> T {
+        const { assert!(DIMS >= 1, "x() needs at least one dimension") };
+        self.0[0]
+    }
+
+    /// The second component. Using it on a vector of fewer than two
+    /// dimensions is a compile-time error.
+    #[inline]
+    pub fn y(&self) -> T {
+        const { assert!(DIMS >= 2, "y() needs at least two dimensions") };
+        self.0[1]
+    }
+
+    /// The third component. Using it on a vector of fewer than three
+    /// dimensions is a compile-time error:
+    /// ```compile_fail
+    /// use rtdriver::Vector;
+    /// Vector([1., 2.]).z();
+    /// ```
+    #[inline]
+    pub fn z(&self) -> T {
+        const { assert!(DIMS >= 3, "z() needs at least three dimensions") };
+        self.0[2]
+    }
+
+    /// Component `i`, or `None` if `i` is not less than `DIMS`.
+    #[inline]
+    pub fn get(&self, i: usize) -> Option<T> {
+        self.0.get(i).copied()
     }
 }
 
@@ -109,17 +321,73 @@ impl<T, const DIMS: usize> From<[T; DIMS]> for Vector<T, DIMS> {
     }
 }
 
+/// Formats as `(x, y, ..)`, each component with its own `Display`. A
+/// precision (`{:.3}`) is applied to every component.
+impl<T: std::fmt::Display, const DIMS: usize> std::fmt::Display for Vector<T, DIMS> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("(")?;
+        for (i, component) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            match f.precision() {
+                Some(precision) => write!(f, "{component:.precision$}")?,
+                None => write!(f, "{component}")?,
+            }
+        }
+        f.write_str(")")
+    }
+}
+
+impl<T, const DIMS: usize> IntoIterator for Vector<T, DIMS> {
+    type Item = T;
+    type IntoIter = std::array::IntoIter<T, DIMS>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, T, const DIMS: usize> IntoIterator for &'a Vector<T, DIMS> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<T, const DIMS: usize> TryFrom<Vec<T>> for Vector<T, DIMS> {
+    type Error = LengthMismatch;
+
+    fn try_from(value: Vec<T>) -> Result<Self, Self::Error> {
+        let actual = value.len();
+        value.try_into().map(Vector).map_err(|_| LengthMismatch {
+            expected: DIMS,
+            actual,
+        })
+    }
+}
+
+impl<T, const DIMS: usize> Vector<T, DIMS> {
+    /// Collects exactly `DIMS` components from `iter`.
+    pub fn try_from_iter(iter: impl IntoIterator<Item = T>) -> Result<Self, LengthMismatch> {
+        iter.into_iter().collect::<Vec<_>>().try_into()
+    }
+}
+
 macro_rules! impl_unary_operation {
     ($op:ident) => {
         paste::paste! {
-            impl<T: Copy, U, const DIMS: usize> std::ops::$op for Vector<T, DIMS>
+            impl<T, U, const DIMS: usize> std::ops::$op for Vector<T, DIMS>
             where
                 T: std::ops::$op<Output = U>,
             {
                 type Output = Vector<U, DIMS>;
 
+                #[inline]
                 fn [< $op:lower >](self) -> Self::Output {
-                    Vector::from_idx(|i| self[i].[< $op:lower >]())
+                    self.map(|a| a.[< $op:lower >]())
                 }
             }
         }
@@ -129,14 +397,66 @@ macro_rules! impl_unary_operation {
 macro_rules! impl_binary_operation {
     ($($op:ident),+$(,)?) => {
         paste::paste! {
-            $(impl<T: Copy, U: Copy, V, const DIMS: usize> std::ops::$op<Vector<U, DIMS>> for Vector<T, DIMS>
+            $(impl<T, U, V, const DIMS: usize> std::ops::$op<Vector<U, DIMS>> for Vector<T, DIMS>
             where
                 T: std::ops::$op<U, Output = V>,
             {
                 type Output = Vector<V, DIMS>;
 
                 fn [< $op:lower >](self, rhs: Vector<U, DIMS>) -> Self::Output {
-                    Vector::from_idx(|i| self[i].[< $op:lower >](rhs[i]))
+                    self.map2(rhs, |a, b| a.[< $op:lower >](b))
+                }
+            })+
+        }
+    };
+}
+
+macro_rules! impl_assign_operation {
+    ($($op:ident),+$(,)?) => {
+        paste::paste! {
+            $(impl<T, U, const DIMS: usize> std::ops::$op<Vector<U, DIMS>> for Vector<T, DIMS>
+            where
+                T: std::ops::$op<U>,
+            {
+                #[inline]
+                fn [< $op:snake >](&mut self, rhs: Vector<U, DIMS>) {
+                    for (a, b) in self.0.iter_mut().zip(rhs.0) {
+                        a.[< $op:snake >](b);
+                    }
+                }
+            })+
+        }
+    };
+}
+
+/// Component-wise operations whose right-hand side has the same component
+/// type, which leaves room for the scalar versions below: `Vector<T>` can
+/// never be `T`, so the two impls cannot overlap.
+macro_rules! impl_scalable_operation {
+    ($($op:ident),+$(,)?) => {
+        paste::paste! {
+            $(impl<T, V, const DIMS: usize> std::ops::$op for Vector<T, DIMS>
+            where
+                T: std::ops::$op<Output = V>,
+            {
+                type Output = Vector<V, DIMS>;
+
+                #[inline]
+                fn [< $op:lower >](self, rhs: Self) -> Self::Output {
+                    self.map2(rhs, |a, b| a.[< $op:lower >](b))
+                }
+            }
+
+            /// Applies the operation between every component and `rhs`.
+            impl<T, V, const DIMS: usize> std::ops::$op<T> for Vector<T, DIMS>
+            where
+                T: Copy + std::ops::$op<Output = V>,
+            {
+                type Output = Vector<V, DIMS>;
+
+                #[inline]
+                fn [< $op:lower >](self, rhs: T) -> Self::Output {
+                    self.map(|a| a.[< $op:lower >](rhs))
                 }
             })+
         }
@@ -144,11 +464,77 @@ macro_rules! impl_binary_operation {
 }
 
 impl_unary_operation!(Neg);
-impl_binary_operation!(Add, Sub, Mul, Div, Rem);
+impl_binary_operation!(Add, Sub, Rem);
+impl_scalable_operation!(Mul, Div);
+impl_assign_operation!(AddAssign, SubAssign);
+
+/// Scales every component by `rhs`.
+impl<T: Copy + std::ops::MulAssign, const DIMS: usize> std::ops::MulAssign<T> for Vector<T, DIMS> {
+    #[inline]
+    fn mul_assign(&mut self, rhs: T) {
+        for a in &mut self.0 {
+            *a *= rhs;
+        }
+    }
+}
+
+/// Vectors are (de)serialized as a tuple of their `DIMS` components.
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use std::marker::PhantomData;
+
+    use serde::{
+        de::{Error, SeqAccess, Visitor},
+        ser::SerializeTuple,
+        Deserialize, Deserializer, Serialize, Serializer,
+    };
+
+    use super::Vector;
+
+    impl<T: Serialize, const DIMS: usize> Serialize for Vector<T, DIMS> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut tuple = serializer.serialize_tuple(DIMS)?;
+            for component in &self.0 {
+                tuple.serialize_element(component)?;
+            }
+            tuple.end()
+        }
+    }
+
+    struct VectorVisitor<T, const DIMS: usize>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>, const DIMS: usize> Visitor<'de> for VectorVisitor<T, DIMS> {
+        type Value = Vector<T, DIMS>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(formatter, "a tuple of {DIMS} components")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut components = Vec::with_capacity(DIMS);
+            while let Some(component) = seq.next_element()? {
+                components.push(component);
+            }
+
+            let len = components.len();
+            match components.try_into() {
+                Ok(components) => Ok(Vector(components)),
+                Err(_) => Err(A::Error::invalid_length(len, &self)),
+            }
+        }
+    }
+
+    impl<'de, T: Deserialize<'de>, const DIMS: usize> Deserialize<'de> for Vector<T, DIMS> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_tuple(DIMS, VectorVisitor(PhantomData))
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::Vector;
+    use crate::error::LengthMismatch;
 
     #[test]
     fn test_addition_u8() {
@@ -261,4 +647,296 @@ mod tests {
 
         assert_eq!(x + y, Vector([1.3, 24.0]));
     }
+
+    #[test]
+    fn test_reductions() {
+        let v = Vector([1.5, -2., 4.]);
+        assert_eq!(v.sum(), 3.5);
+        assert_eq!(v.product(), -12.);
+        assert_eq!(Vector([3usize; 4]).product(), 3usize.pow(4));
+        assert_eq!(Vector::<i32, 0>([]).sum(), 0);
+        assert_eq!(Vector::<i32, 0>([]).product(), 1);
+        #[allow(deprecated)]
+        let prod = v.prod();
+        assert_eq!(prod, v.sum());
+
+        assert_eq!(v.fold(f64::NEG_INFINITY, f64::max), 4.);
+        assert_eq!(Vector([3, 9, -1]).fold(i32::MIN, i32::max), 9);
+        assert_eq!(Vector([1, 2, 3]).fold(0, |n, i| 10 * n + i), 123);
+        assert_eq!(Vector([true, false]).fold(0, |n, b| n + b as usize), 1);
+    }
+
+    #[test]
+    fn test_compensated_sum() {
+        // Every small term is below half an ulp of one, so a naive sum
+        // rounds each of them away.
+        let mut v = Vector([1e-8f32; 1001]);
+        v[0] = 1.;
+        assert_eq!(v.sum(), 1.);
+        assert_eq!(v.sum_kahan(), 1.00001);
+        assert_eq!(Vector([0.1; 10]).sum_kahan(), 1.);
+        assert_eq!(Vector::<f64, 0>([]).sum_kahan(), 0.);
+    }
+
+    #[test]
+    fn test_negation() {
+        let v = Vector([1.5, -2., 0.]);
+        assert_eq!(-v, Vector([-1.5, 2., -0.]));
+        assert_eq!(-(-v), v);
+        assert_eq!(-Vector::<f64, 3>::zero(), Vector::zero());
+        assert_eq!(-Vector([3, -4]), Vector([-3, 4]));
+        assert_eq!(-(v * 2.), v * -2.);
+    }
+
+    #[test]
+    fn test_scalar_operations() {
+        let v = Vector([1.5, -2., 4.]);
+        assert_eq!(v * 2., Vector([3., -4., 8.]));
+        assert_eq!(v * -0.5, Vector([-0.75, 1., -2.]));
+        assert_eq!(v / 2., Vector([0.75, -1., 2.]));
+        assert_eq!(v / -4., Vector([-0.375, 0.5, -1.]));
+        assert_eq!(v * 3. / 3., v);
+        assert_eq!(v * 2., v * Vector::broadcast(2.));
+
+        assert_eq!(Vector([7, -3]) * -2, Vector([-14, 6]));
+        assert_eq!(Vector([7, -3]) / 2, Vector([3, -1]));
+        assert!((Vector([1., -2.]) / 0.).iter().all(f64::is_infinite));
+    }
+
+    #[test]
+    fn test_assign_operations() {
+        let a = Vector([1.5, -2., 4.]);
+        let b = Vector([0.25, 3., -1.]);
+
+        let mut v = a;
+        v += b;
+        assert_eq!(v, a + b);
+
+        let mut v = a;
+        v -= b;
+        assert_eq!(v, a - b);
+
+        let mut v = a;
+        v *= 2.5;
+        assert_eq!(v, a.map(|x| x * 2.5));
+
+        let mut v = Vector([7u32, 1]);
+        v += Vector([1, 2]);
+        v -= Vector([3, 3]);
+        v *= 4;
+        assert_eq!(v, Vector([20, 0]));
+    }
+
+    #[test]
+    fn test_component_accessors() {
+        let v = Vector([1.5, -2., 4.]);
+        assert_eq!((v.x(), v.y(), v.z()), (1.5, -2., 4.));
+        assert_eq!(Vector([3, 7]).y(), 7);
+        assert_eq!(Vector([9u8]).x(), 9);
+
+        assert_eq!(v.get(0), Some(1.5));
+        assert_eq!(v.get(2), Some(4.));
+        assert_eq!(v.get(3), None);
+        assert_eq!(v.get(usize::MAX), None);
+        assert_eq!(Vector::<f64, 0>([]).get(0), None);
+    }
+
+    #[test]
+    fn test_dot() {
+        let x = Vector([1., -2., 3.]);
+        let y = Vector([4., 0.5, -1.]);
+
+        assert_eq!(x.dot(y), 0.);
+        assert_eq!(x.dot(x), 14.);
+        assert_eq!(Vector([2, 3]).dot(Vector([-1, 4])), 10);
+        assert_eq!(Vector::<f64, 3>::zero().dot(x), 0.);
+    }
+
+    #[test]
+    fn test_orthogonal_basis() {
+        let basis: [Vector<f64, 3>; 3] =
+            std::array::from_fn(|i| Vector::from_idx(|j| (i == j) as u8 as f64));
+
+        for (i, &e) in basis.iter().enumerate() {
+            assert_eq!(e.norm(), 1.);
+            for (j, &f) in basis.iter().enumerate() {
+                assert_eq!(e.dot(f), (i == j) as u8 as f64);
+            }
+        }
+    }
+
+    #[test]
+    fn test_norm() {
+        assert_eq!(Vector([3f32, -4.]).norm_squared(), 25.);
+        assert_eq!(Vector([3f32, -4.]).norm(), 5.);
+        assert_eq!(Vector::<f64, 4>::zero().norm(), 0.);
+        assert_eq!(Vector::<f64, 4>::zero().norm_squared(), 0.);
+    }
+
+    #[test]
+    fn test_normalized() {
+        let unit = Vector([3f64, -4.]).normalized().unwrap();
+        assert_eq!(unit, Vector([0.6, -0.8]));
+        assert!((unit.norm() - 1.).abs() < 1e-15);
+        assert_eq!(
+            Vector([0., 2., 0.]).normalize_or_zero(),
+            Vector([0., 1., 0.])
+        );
+    }
+
+    #[test]
+    fn test_normalized_zero() {
+        assert_eq!(Vector::<f32, 3>::zero().normalized(), None);
+        assert_eq!(Vector::<f32, 3>::zero().normalize_or_zero(), Vector::zero());
+        assert_eq!(Vector([f64::NAN, 1.]).normalized(), None);
+    }
+
+    #[test]
+    fn test_normalized_near_epsilon() {
+        let eps = f64::EPSILON;
+
+        assert_eq!(Vector([eps, 0.]).normalized(), None);
+        assert_eq!(Vector([0., eps / 2.]).normalize_or_zero(), Vector::zero());
+        assert_eq!(Vector([2. * eps, 0.]).normalized(), Some(Vector([1., 0.])));
+    }
+
+    #[test]
+    fn test_cross_basis() {
+        let [x, y, z] = [Vector([1, 0, 0]), Vector([0, 1, 0]), Vector([0, 0, 1])];
+
+        assert_eq!(x.cross(y), z);
+        assert_eq!(y.cross(z), x);
+        assert_eq!(z.cross(x), y);
+    }
+
+    #[test]
+    fn test_cross_identities() {
+        let u = Vector([1.5f64, -2., 0.25]);
+        let v = Vector([-3., 0.5, 4.]);
+
+        assert_eq!(u.cross(v), -v.cross(u));
+        assert_eq!(u.cross(u), Vector::zero());
+        assert_eq!(u.cross(v).dot(u), 0.);
+        assert_eq!(u.cross(v).dot(v), 0.);
+    }
+
+    #[test]
+    fn test_min_max() {
+        let x = Vector([-1.5f64, 2., 0.]);
+        let y = Vector([0.5, -3., -0.]);
+
+        assert_eq!(x.min(y), Vector([-1.5, -3., 0.]));
+        assert_eq!(x.max(y), Vector([0.5, 2., 0.]));
+        assert_eq!(Vector([-4i32, 7]).min(Vector([3, -7])), Vector([-4, -7]));
+        assert_eq!(Vector([-4i32, 7]).max(Vector([3, -7])), Vector([3, 7]));
+    }
+
+    #[test]
+    fn test_clamp() {
+        let lo = Vector([-1., -1.]);
+        let hi = Vector([1., 2.]);
+
+        assert_eq!(Vector([-3., 5.]).clamp(lo, hi), Vector([-1., 2.]));
+        assert_eq!(Vector([0.5, -0.5]).clamp(lo, hi), Vector([0.5, -0.5]));
+    }
+
+    #[test]
+    fn test_clamp_inverted_bounds() {
+        let lo = Vector([2, 0]);
+        let hi = Vector([-2, 5]);
+
+        assert_eq!(Vector([0, 3]).clamp(lo, hi), Vector([-2, 3]));
+        assert_eq!(Vector([9, -9]).clamp(lo, hi), Vector([-2, 0]));
+    }
+
+    #[test]
+    fn test_map2() {
+        let x = Vector([3i32, -7, 10]);
+        let y = Vector([2u8, 4, 0]);
+
+        assert_eq!(x.map2(y, |a, b| a.pow(b as u32)), Vector([9, 2401, 1]));
+        assert_eq!(x.map2(y, |a, b| (a, b)), Vector([(3, 2), (-7, 4), (10, 0)]));
+    }
+
+    #[test]
+    fn test_operators_on_non_copy() {
+        let x = Vector([String::from("a"), String::from("b")]);
+        let y = Vector(["c", "d"]);
+
+        assert_eq!(x + y, Vector([String::from("ac"), String::from("bd")]));
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let x = Vector([1, -2, 3]);
+
+        let by_ref: Vec<_> = (&x).into_iter().copied().collect();
+        assert_eq!(by_ref, [1, -2, 3]);
+
+        let mut sum = 0;
+        for component in x {
+            sum += component;
+        }
+        assert_eq!(sum, 2);
+    }
+
+    #[test]
+    fn test_try_from_iter() {
+        let x = Vector([1.5, -2., 0.25]);
+
+        assert_eq!(Vector::try_from_iter(x), Ok(x));
+        assert_eq!(
+            Vector::try_from_iter(x.into_iter().map(|c| c * 2.)),
+            Ok(x.map(|c| c * 2.))
+        );
+        assert_eq!(Vector::try_from(vec![1, 2]), Ok(Vector([1, 2])));
+    }
+
+    #[test]
+    fn test_try_from_wrong_length() {
+        assert_eq!(
+            Vector::<i32, 3>::try_from(vec![1, 2]),
+            Err(LengthMismatch {
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            Vector::<i32, 2>::try_from_iter(0..5),
+            Err(LengthMismatch {
+                expected: 2,
+                actual: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Vector([1.5, -2.25]).to_string(), "(1.5, -2.25)");
+        assert_eq!(Vector([1, 0, -3]).to_string(), "(1, 0, -3)");
+        assert_eq!(Vector::<f64, 0>([]).to_string(), "()");
+    }
+
+    #[test]
+    fn test_display_precision() {
+        let x = Vector([1., 2.0 / 3.0]);
+
+        assert_eq!(format!("{x:.2}"), "(1.00, 0.67)");
+        assert_eq!(
+            format!("{:.3}", Vector([0.5f32, -1., 1e-4])),
+            "(0.500, -1.000, 0.000)"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let x = Vector([1.5f64, -2., 0.25]);
+        let json = serde_json::to_string(&x).unwrap();
+        assert_eq!(json, "[1.5,-2.0,0.25]");
+        assert_eq!(serde_json::from_str::<Vector<f64, 3>>(&json).unwrap(), x);
+
+        assert!(serde_json::from_str::<Vector<f64, 2>>(&json).is_err());
+        assert!(serde_json::from_str::<Vector<f64, 4>>(&json).is_err());
+    }
 }
@@ -0,0 +1,96 @@
+//! JavaScript bindings through [`wasm_bindgen`](mod@wasm_bindgen), enabled
+//! by the `wasm` feature. The library allocates through the global
+//! allocator of whatever links it, so nothing else changes for `wasm32`.
+//! Rayon's threads are not available in the browser, so build without
+//! default features:
+//!
+//! ```text
+//! cargo check --lib --target wasm32-unknown-unknown --no-default-features --features wasm
+//! ```
+//!
+//! The `target-cpu=native` flag in `.cargo/config.toml` only draws warnings
+//! about unknown CPU features there. The binary stays native-only.
+
+use wasm_bindgen::prelude::*;
+
+use crate::Simulation;
+
+/// Side of the lattice of a [`WasmSimulation`].
+pub const SIZE: usize = 64;
+
+/// A two-dimensional `f32` [`Simulation`] of side [`SIZE`], exposed to
+/// JavaScript as `Simulation`.
+#[wasm_bindgen(js_name = Simulation)]
+pub struct WasmSimulation {
+    inner: Simulation<f32, SIZE>,
+}
+
+#[wasm_bindgen(js_class = Simulation)]
+impl WasmSimulation {
+    /// A lattice at rest with the given spring constants and the other
+    /// parameters at their defaults. Throws if either is negative or NaN.
+    #[wasm_bindgen(constructor)]
+    pub fn new(stiffness: f32, origin_stiffness: f32) -> Result<WasmSimulation, JsError> {
+        let inner = Simulation::build()
+            .stiffness(stiffness)
+            .origin_stiffness(origin_stiffness)
+            .try_finish()?;
+        Ok(WasmSimulation { inner })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        SIZE
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn time(&self) -> f32 {
+        self.inner.time()
+    }
+
+    /// Displaces the particle at `(i, j)` to `(x, y)`. Throws outside the
+    /// grid.
+    pub fn displace(&mut self, i: usize, j: usize, x: f32, y: f32) -> Result<(), JsError> {
+        Ok(self.inner.set_position([i, j].into(), [x, y].into())?)
+    }
+
+    /// Takes `steps` steps of size `dt`.
+    pub fn step(&mut self, steps: usize, dt: f32) {
+        self.inner.run(steps, dt);
+    }
+
+    /// The positions, flattened to `x, y` pairs in row-major particle
+    /// order, as a new `Float32Array`.
+    pub fn positions(&self) -> Vec<f32> {
+        self.inner.particles().flat_map(|(_, pos, _)| pos).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WasmSimulation, SIZE};
+    use crate::{Simulation, Vector};
+
+    #[test]
+    fn test_step_api() {
+        let mut wasm = WasmSimulation::new(2., 0.5).unwrap();
+        let mut sim = Simulation::<f32, SIZE>::build()
+            .stiffness(2.)
+            .origin_stiffness(0.5)
+            .finish();
+        assert_eq!(wasm.positions(), vec![0.; SIZE * SIZE * 2]);
+
+        wasm.displace(3, 5, 1., -0.5).unwrap();
+        sim.set_position(Vector([3, 5]), Vector([1., -0.5]))
+            .unwrap();
+        let flat = wasm.positions();
+        assert_eq!(flat[(3 * SIZE + 5) * 2..][..2], [1., -0.5]);
+
+        wasm.step(10, 1e-2);
+        sim.run(10, 1e-2);
+        assert_eq!(wasm.time(), sim.time());
+        let expected: Vec<f32> = sim.particles().flat_map(|(_, pos, _)| pos).collect();
+        assert_eq!(wasm.positions(), expected);
+        assert_eq!(wasm.size(), SIZE);
+    }
+}
@@ -0,0 +1,39 @@
+"""Smoke test of the Python bindings, run with pytest after `maturin develop`."""
+
+import numpy as np
+import pytest
+
+import rtdriver
+
+
+def test_build_and_run():
+    sim = rtdriver.Simulation.build(16, stiffness=2.0, boundary="periodic")
+    assert sim.size == 16
+    assert 16 in rtdriver.SIZES
+
+    sim.set_position((3, 4), (0.5, -0.25))
+    positions = sim.positions()
+    assert positions.shape == (16, 16, 2)
+    assert positions.dtype == np.float64
+    assert tuple(positions[3, 4]) == (0.5, -0.25)
+    assert np.count_nonzero(positions) == 2
+
+    energy = sim.total_energy()
+    assert energy == sim.kinetic_energy() + sim.potential_energy()
+    sim.update(1e-2)
+    sim.run(99, 1e-2)
+    assert sim.steps == 100
+    assert sim.time == pytest.approx(1.0)
+    assert sim.total_energy() == pytest.approx(energy, rel=1e-2)
+    assert np.abs(sim.velocities()).sum() > 0
+
+
+def test_errors():
+    with pytest.raises(ValueError):
+        rtdriver.Simulation.build(5)
+    with pytest.raises(ValueError):
+        rtdriver.Simulation.build(8, mass=0.0)
+    with pytest.raises(ValueError):
+        rtdriver.Simulation.build(8, boundary="open")
+    with pytest.raises(IndexError):
+        rtdriver.Simulation.build(8).set_position((8, 0), (1.0, 0.0))